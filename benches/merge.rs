@@ -0,0 +1,120 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use kubemerge::config::{
+    Cluster, Context, KubeConfig, NamedCluster, NamedContext, NamedUser, User,
+};
+use kubemerge::merge::{MergeOptions, merge_configs, parse_kubeconfig};
+use std::path::{Path, PathBuf};
+
+/// Builds a config with `entries` clusters, contexts, and users, named so
+/// that configs built with different `offset`s do not overlap.
+fn generate_config(offset: usize, entries: usize) -> KubeConfig {
+    let names: Vec<String> = (offset..offset + entries)
+        .map(|i| format!("env-{}", i))
+        .collect();
+
+    let mut config = KubeConfig::new();
+    config.clusters = Some(
+        names
+            .iter()
+            .map(|name| NamedCluster {
+                name: name.clone(),
+                cluster: Cluster::new(format!("https://{}.example.com:6443", name)),
+            })
+            .collect(),
+    );
+    config.contexts = Some(
+        names
+            .iter()
+            .map(|name| {
+                let mut context = Context::new(name.clone(), name.clone());
+                context.namespace = Some("default".to_string());
+                NamedContext {
+                    name: name.clone(),
+                    context,
+                }
+            })
+            .collect(),
+    );
+    config.users = Some(
+        names
+            .iter()
+            .map(|name| {
+                let mut user = User::default();
+                user.token = Some(format!("token-{}", name));
+                NamedUser {
+                    name: name.clone(),
+                    user,
+                }
+            })
+            .collect(),
+    );
+    config.current_context = names.first().cloned().unwrap_or_default();
+    config
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let options = MergeOptions::default();
+
+    let single = vec![(PathBuf::from("single.yaml"), generate_config(0, 2000))];
+    c.bench_function("merge single file (2000 entries)", |b| {
+        b.iter_batched(
+            || single.clone(),
+            |configs| merge_configs(configs, &options).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    let many: Vec<_> = (0..20)
+        .map(|i| {
+            (
+                PathBuf::from(format!("file-{}.yaml", i)),
+                generate_config(i * 100, 100),
+            )
+        })
+        .collect();
+    c.bench_function("merge 20 files (2000 entries)", |b| {
+        b.iter_batched(
+            || many.clone(),
+            |configs| merge_configs(configs, &options).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    // Each file repeats half of the previous one, so every other entry is
+    // a duplicate to look up and skip.
+    let overlapping: Vec<_> = (0..40)
+        .map(|i| {
+            (
+                PathBuf::from(format!("file-{}.yaml", i)),
+                generate_config(i * 100, 200),
+            )
+        })
+        .collect();
+    c.bench_function("merge 40 overlapping files (4100 entries)", |b| {
+        b.iter_batched(
+            || overlapping.clone(),
+            |configs| merge_configs(configs, &options).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let options = MergeOptions::default();
+
+    // Covers the checks every input goes through before parsing, such as
+    // encryption detection.
+    let content = serde_yml::to_string(&generate_config(0, 2000))
+        .unwrap()
+        .into_bytes();
+    c.bench_function("parse file (2000 entries)", |b| {
+        b.iter_batched(
+            || content.clone(),
+            |content| parse_kubeconfig(Path::new("single.yaml"), content, &options).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_merge, bench_parse);
+criterion_main!(benches);
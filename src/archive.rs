@@ -0,0 +1,93 @@
+use crate::utils::{ScanOptions, is_yaml_file, should_exclude};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const EMPTY_ZIP_MAGIC: &[u8] = b"PK\x05\x06";
+
+/// A kubeconfig read from inside an archive. The path is the archive path
+/// joined with the member's name, e.g. `bundle.zip/prod/config.yaml`.
+pub type ArchiveMember = (PathBuf, Vec<u8>);
+
+/// Members found by [`read_archive`], along with the paths of those dropped
+/// by an exclude pattern.
+#[derive(Debug, Default)]
+pub struct ArchiveContents {
+    pub members: Vec<ArchiveMember>,
+    pub excluded: Vec<PathBuf>,
+}
+
+/// Reports whether `path` is an archive kubemerge can read members from.
+pub fn is_archive(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| magic == ZIP_MAGIC || magic == EMPTY_ZIP_MAGIC)
+}
+
+/// Reads the config members of an archive, in archive order. Members are
+/// selected and excluded like files in a directory scan, with `.json`
+/// always accepted; directories and other members are skipped. Two members
+/// with the same path are an error, as only one of them could be merged.
+pub fn read_archive(
+    path: &Path,
+    options: &ScanOptions,
+) -> Result<ArchiveContents, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Failed to read archive {}: {}", path.display(), e))?;
+
+    let mut contents = ArchiveContents::default();
+    for index in 0..archive.len() {
+        let mut member = archive
+            .by_index(index)
+            .map_err(|e| format!("Failed to read archive {}: {}", path.display(), e))?;
+        if member.is_dir() {
+            continue;
+        }
+        // Names that would escape the archive root are not trusted.
+        let Some(name) = member.enclosed_name() else {
+            debug!("Skipping unsafe archive member name: {}", member.name());
+            continue;
+        };
+        let is_config =
+            is_yaml_file(&name, options) || name.extension().is_some_and(|ext| ext == "json");
+        if !is_config || name.starts_with("__MACOSX") {
+            debug!("Skipping archive member: {}", name.display());
+            continue;
+        }
+        let member_path = path.join(&name);
+        if should_exclude(&name, &options.exclude_patterns) {
+            debug!("Excluded archive member: {}", name.display());
+            contents.excluded.push(member_path);
+            continue;
+        }
+        if contents
+            .members
+            .iter()
+            .any(|(seen, _)| *seen == member_path)
+        {
+            return Err(format!(
+                "Archive {} has more than one member at {}",
+                path.display(),
+                name.display()
+            )
+            .into());
+        }
+
+        let mut content = Vec::new();
+        member.read_to_end(&mut content).map_err(|e| {
+            format!(
+                "Failed to read {} from {}: {}",
+                name.display(),
+                path.display(),
+                e
+            )
+        })?;
+        contents.members.push((member_path, content));
+    }
+    Ok(contents)
+}
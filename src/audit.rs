@@ -0,0 +1,102 @@
+use crate::certs::load_certificate;
+use crate::config::{Context, KubeConfig, NamedCluster, NamedContext};
+use crate::merge::{EntryKind, Sources};
+use crate::utils::server_host;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// Runs the read-only audit checks over a merged config and logs findings.
+pub fn audit_config(config: &KubeConfig, sources: &Sources) {
+    audit_namespace_duplicates(config);
+    audit_shared_ca_clusters(config, sources);
+}
+
+fn audit_namespace_duplicates(config: &KubeConfig) {
+    let groups = namespace_only_duplicates(config);
+    if groups.is_empty() {
+        info!("Audit: no contexts differ only by namespace");
+    }
+
+    for group in groups {
+        info!(
+            "Audit: contexts for cluster '{}' and user '{}' differ only by namespace:",
+            group[0].context.cluster, group[0].context.user
+        );
+        for context in group {
+            info!(
+                "  - {} (namespace: {})",
+                context.name,
+                context.context.namespace.as_deref().unwrap_or("<none>")
+            );
+        }
+    }
+}
+
+fn audit_shared_ca_clusters(config: &KubeConfig, sources: &Sources) {
+    let groups = shared_ca_clusters(config);
+    if groups.is_empty() {
+        info!("Audit: no clusters share a CA under different server hosts");
+    }
+
+    for group in groups {
+        info!(
+            "Audit: clusters with the same CA but different server hosts, likely the same cluster:"
+        );
+        for cluster in group {
+            let source = sources
+                .get(&(EntryKind::Cluster, cluster.name.clone()))
+                .map_or_else(|| "unknown source".to_string(), |s| s.display().to_string());
+            info!(
+                "  - {} (server: {}, from {})",
+                cluster.name, cluster.cluster.server, source
+            );
+        }
+    }
+}
+
+/// Groups clusters whose CA certificate is byte-identical but whose server
+/// hosts differ, such as one cluster reached by internal and external DNS
+/// names. Clusters without a readable CA are left out.
+fn shared_ca_clusters(config: &KubeConfig) -> Vec<Vec<&NamedCluster>> {
+    let mut by_ca: BTreeMap<Vec<u8>, Vec<&NamedCluster>> = BTreeMap::new();
+    for cluster in config.clusters.iter().flatten() {
+        let certificate = load_certificate(
+            cluster.cluster.certificate_authority_data.as_deref(),
+            cluster.cluster.certificate_authority.as_deref(),
+        );
+        if let Ok(Some(bytes)) = certificate {
+            by_ca.entry(bytes).or_default().push(cluster);
+        }
+    }
+
+    by_ca
+        .into_values()
+        .filter(|members| {
+            let host = |cluster: &NamedCluster| {
+                server_host(&cluster.cluster.server).map(str::to_ascii_lowercase)
+            };
+            let first = host(members[0]);
+            members.iter().any(|cluster| host(cluster) != first)
+        })
+        .collect()
+}
+
+/// Groups contexts that are identical apart from their namespace.
+fn namespace_only_duplicates(config: &KubeConfig) -> Vec<Vec<&NamedContext>> {
+    let mut groups: Vec<(Context, Vec<&NamedContext>)> = Vec::new();
+
+    for context in config.contexts.iter().flatten() {
+        let mut key = context.context.clone();
+        key.namespace = None;
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, members)) => members.push(context),
+            None => groups.push((key, vec![context])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
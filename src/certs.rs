@@ -0,0 +1,43 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use std::fs;
+use x509_parser::parse_x509_certificate;
+use x509_parser::pem::parse_x509_pem;
+
+/// Loads certificate bytes from an inline base64 `*-data` field, falling
+/// back to the referenced file. Returns `Ok(None)` when neither is set.
+pub fn load_certificate(data: Option<&str>, path: Option<&str>) -> Result<Option<Vec<u8>>, String> {
+    if let Some(data) = data {
+        return STANDARD
+            .decode(data.trim())
+            .map(Some)
+            .map_err(|e| format!("invalid base64 certificate data: {}", e));
+    }
+    if let Some(path) = path {
+        return fs::read(path)
+            .map(Some)
+            .map_err(|e| format!("failed to read certificate {}: {}", path, e));
+    }
+    Ok(None)
+}
+
+/// Returns the `notAfter` time of the first certificate in PEM or DER bytes.
+pub fn certificate_expiry(bytes: &[u8]) -> Result<DateTime<Utc>, String> {
+    let timestamp = match parse_x509_pem(bytes) {
+        Ok((_, pem)) => pem
+            .parse_x509()
+            .map_err(|e| format!("invalid certificate: {}", e))?
+            .validity()
+            .not_after
+            .timestamp(),
+        Err(_) => parse_x509_certificate(bytes)
+            .map_err(|e| format!("invalid certificate: {}", e))?
+            .1
+            .validity()
+            .not_after
+            .timestamp(),
+    };
+    DateTime::from_timestamp(timestamp, 0)
+        .ok_or_else(|| "certificate expiry out of range".to_string())
+}
@@ -0,0 +1,724 @@
+use clap::{Arg, Command};
+use kubemerge::decrypt::parse_age_recipient;
+use kubemerge::rename::parse_context_template;
+use kubemerge::select::parse_selector;
+use kubemerge::utils::{DEFAULT_HEADER, parse_duration};
+use kubemerge::validate::DEFAULT_NAME_PATTERN;
+use regex::Regex;
+
+pub fn build_cli(home_dir: &str) -> Command {
+    Command::new("kubemerge")
+        .version("0.2.0")
+        .args_override_self(true)
+        .about("Merges multiple kubeconfig YAML files into a single file")
+        .arg(
+            Arg::new("profile")
+                .global(true)
+                .short('p')
+                .long("profile")
+                .value_name("NAME")
+                .help("Use the named profile from the kubemerge config file as default options"),
+        )
+        .arg(
+            Arg::new("config")
+                .global(true)
+                .long("config")
+                .value_name("FILE")
+                .help("kubemerge config file holding profiles")
+                .default_value(default_config_path(home_dir)),
+        )
+        .arg(
+            Arg::new("input")
+                .global(true)
+                .short('i')
+                .long("input")
+                .value_name("DIR")
+                .help("Input directory containing kubeconfig files, a single kubeconfig file, or a glob pattern with shell-style braces such as '~/.kube/{prod,staging}/*.yaml'")
+                .default_value(format!("{}/.kube", home_dir)),
+        )
+        .arg(
+            Arg::new("output")
+                .global(true)
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("Output file path (strftime placeholders such as %Y-%m-%d are expanded), or fd:N to write to an inherited file descriptor")
+                .default_value(format!("{}/.kube/config", home_dir)),
+        )
+        .arg(
+            Arg::new("exclude")
+                .global(true)
+                .short('e')
+                .long("exclude")
+                .value_name("PATTERN")
+                .help("Exclude files matching pattern")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("priority-file")
+                .global(true)
+                .long("priority-file")
+                .value_name("FILE")
+                .help("Merge the files listed in FILE first, in the listed order"),
+        )
+        .arg(
+            Arg::new("primary-file")
+                .global(true)
+                .long("primary-file")
+                .value_name("FILE")
+                .help("Take current-context from FILE if it sets one, instead of from the first file that does"),
+        )
+        .arg(
+            Arg::new("no-duplicates")
+                .global(true)
+                .long("no-duplicates")
+                .help("Fail if the same cluster, context, or user name appears in more than one file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("extensions")
+                .global(true)
+                .long("extensions")
+                .value_name("EXT,...")
+                .help("Comma-separated file extensions to treat as kubeconfigs")
+                .value_delimiter(',')
+                .action(clap::ArgAction::Append)
+                .default_value("yaml,yml"),
+        )
+        .arg(
+            Arg::new("include-extensionless")
+                .global(true)
+                .long("include-extensionless")
+                .help("Also treat files without an extension as kubeconfigs")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files-from")
+                .global(true)
+                .long("files-from")
+                .value_name("FILE")
+                .help("Read newline-delimited kubeconfig paths from FILE ('-' for stdin) instead of scanning the input directory"),
+        )
+        .arg(
+            Arg::new("input-format")
+                .global(true)
+                .long("input-format")
+                .value_name("FORMAT")
+                .help("Parse input files as YAML or JSON, or sniff the content")
+                .value_parser(["auto", "yaml", "json"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("read-timeout")
+                .global(true)
+                .long("read-timeout")
+                .value_name("DURATION")
+                .help("Skip input files that take longer than DURATION to read (e.g. 500ms, 5s)")
+                .value_parser(parse_duration),
+        )
+        .arg(
+            Arg::new("decrypt")
+                .global(true)
+                .long("decrypt")
+                .help("Decrypt age and sops encrypted input files in memory before parsing")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("expand-env")
+                .global(true)
+                .long("expand-env")
+                .help("Expand $VAR and ${VAR} references in server URLs and certificate paths")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allow-undefined")
+                .global(true)
+                .long("allow-undefined")
+                .help("Leave references to undefined variables untouched instead of failing")
+                .requires("expand-env")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("namespace-conflict")
+                .global(true)
+                .long("namespace-conflict")
+                .value_name("STRATEGY")
+                .help("How to handle same-named contexts with different namespaces")
+                .value_parser(["first", "specific", "error"])
+                .default_value("first"),
+        )
+        .arg(
+            Arg::new("union-duplicate-contexts")
+                .global(true)
+                .long("union-duplicate-contexts")
+                .help("Combine same-named contexts that target the same cluster and user instead of keeping only the first")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("deep-merge-preferences")
+                .global(true)
+                .long("deep-merge-preferences")
+                .help("Merge nested preferences key by key and union preference lists instead of replacing them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore-preferences")
+                .global(true)
+                .long("ignore-preferences")
+                .help("Drop the preferences of every input, leaving the output without a preferences block")
+                .conflicts_with("deep-merge-preferences")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict-io")
+                .global(true)
+                .long("strict-io")
+                .help("Abort on files that cannot be read because of their permissions instead of skipping them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("drop-expired")
+                .global(true)
+                .long("drop-expired")
+                .help("Leave out users whose client certificate has already expired")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("token-denylist")
+                .global(true)
+                .long("token-denylist")
+                .value_name("FILE")
+                .help("Leave out users whose token is listed in FILE, as plaintext or sha256:<hex>, one per line; with --strict, fail instead")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("api-version")
+                .global(true)
+                .long("api-version")
+                .value_name("VERSION")
+                .help("apiVersion to write (defaults to the primary file's, or the one all inputs share, else v1)")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("trim-whitespace")
+                .global(true)
+                .long("trim-whitespace")
+                .help("Trim surrounding whitespace from names, servers, tokens, and file paths before merging, reporting each value changed")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("prefer-fresher-tokens")
+                .global(true)
+                .long("prefer-fresher-tokens")
+                .help("Of same-named users whose auth-provider or JWT tokens carry an expiry, keep the one expiring last instead of the first")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("case-insensitive-names")
+                .global(true)
+                .long("case-insensitive-names")
+                .help("Treat cluster, context, and user names that differ only in case as duplicates, keeping the first casing seen")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sync")
+                .global(true)
+                .long("sync")
+                .help("Reconcile the output with the inputs: drop entries whose source no longer provides them and keep entries kubemerge did not write")
+                .requires("state-file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("state-file")
+                .global(true)
+                .long("state-file")
+                .value_name("FILE")
+                .help("JSON file recording which entries --sync wrote and where they came from"),
+        )
+        .arg(
+            Arg::new("select")
+                .global(true)
+                .long("select")
+                .value_name("KEY=VALUE")
+                .help("Keep only contexts annotated with KEY=VALUE (also matched as kubemerge.io/KEY), with the clusters and users they use; repeat to require several")
+                .value_parser(parse_selector)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("allow-servers")
+                .global(true)
+                .long("allow-servers")
+                .value_name("FILE")
+                .help("Merge only clusters whose server host is listed in FILE, one per line, with the contexts and users they need")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("pin")
+                .global(true)
+                .long("pin")
+                .value_name("NAME")
+                .help("Keep the clusters, contexts, and users named NAME exactly as they are in the existing output, over any merged entry and exempt from --sync removal; repeat to pin several")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("pins-file")
+                .global(true)
+                .long("pins-file")
+                .value_name("FILE")
+                .help("Read names to pin from FILE, one per line")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("schema")
+                .global(true)
+                .long("schema")
+                .value_name("PATH")
+                .help("Validate the merged config against the JSON Schema in PATH and fail on any violation")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("transform-script")
+                .global(true)
+                .long("transform-script")
+                .value_name("PATH")
+                .help("Executable run on every merged entry: reads the entry as JSON on stdin and prints its replacement"),
+        )
+        .arg(
+            Arg::new("metadata-file")
+                .global(true)
+                .long("metadata-file")
+                .value_name("FILE")
+                .help("YAML file mapping context names to annotations added to the context as kubemerge.io/<key>"),
+        )
+        .arg(
+            Arg::new("from-secrets")
+                .global(true)
+                .long("from-secrets")
+                .help("Read kubeconfigs embedded in Kubernetes Secret manifests (data.config, stringData.kubeconfig, ...)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("relative-cert-paths")
+                .global(true)
+                .long("relative-cert-paths")
+                .help("Resolve relative certificate and key paths against the directory of their source file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("prefix")
+                .global(true)
+                .long("prefix")
+                .value_name("PREFIX")
+                .help("Prepend PREFIX to every cluster, context, and user name"),
+        )
+        .arg(
+            Arg::new("context-suffix")
+                .global(true)
+                .long("context-suffix")
+                .help("Append '@<server host>' of the referenced cluster to every context name")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("context-template")
+                .global(true)
+                .long("context-template")
+                .value_name("TEMPLATE")
+                .help("Name every context after TEMPLATE, e.g. '{cluster}/{user}/{namespace}'; placeholders are {name}, {cluster}, {user}, and {namespace}")
+                .value_parser(parse_context_template)
+                .conflicts_with("context-suffix"),
+        )
+        .arg(
+            Arg::new("template-default")
+                .global(true)
+                .long("template-default")
+                .value_name("VALUE")
+                .help("Value for {namespace} in --context-template when a context has none")
+                .requires("context-template")
+                .default_value(""),
+        )
+        .arg(
+            Arg::new("annotate-source")
+                .global(true)
+                .long("annotate-source")
+                .help("Record the file each cluster, context, and user came from under 'kubemerge.io/source'")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("minify")
+                .global(true)
+                .long("minify")
+                .help("Drop clusters and users that no context references")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fail-if-empty")
+                .global(true)
+                .long("fail-if-empty")
+                .help("Fail if the merged config has no clusters, contexts, or users at all")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-clusters")
+                .global(true)
+                .long("max-clusters")
+                .value_name("N")
+                .help("Fail if the merged config has more than N clusters")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-contexts")
+                .global(true)
+                .long("max-contexts")
+                .value_name("N")
+                .help("Fail if the merged config has more than N contexts")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-users")
+                .global(true)
+                .long("max-users")
+                .value_name("N")
+                .help("Fail if the merged config has more than N users")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("coalesce-clusters")
+                .global(true)
+                .long("coalesce-clusters")
+                .help("Fold clusters with the same server, TLS server name, and CA into one and point their contexts at it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clear-current-context")
+                .global(true)
+                .long("clear-current-context")
+                .help("Omit current-context from the merged output")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("use-context")
+                .global(true)
+                .long("use-context")
+                .value_name("NAME")
+                .help("Switch to context NAME after merging, like kubectl config use-context; fails if the merged config has no such context")
+                .conflicts_with("clear-current-context")
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("require-current-context")
+                .global(true)
+                .long("require-current-context")
+                .help("Fail when the merged config ends up without a current-context")
+                .conflicts_with("clear-current-context")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-reference-check")
+                .global(true)
+                .long("no-reference-check")
+                .help("Skip checking that contexts reference existing clusters and users")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("validate-names")
+                .global(true)
+                .long("validate-names")
+                .help("Warn about cluster, context, and user names that do not match --name-pattern")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("name-pattern")
+                .global(true)
+                .long("name-pattern")
+                .value_name("REGEX")
+                .help("Pattern names must match in full for --validate-names (default: RFC 1123 subdomain)")
+                .requires("validate-names")
+                .value_parser(parse_name_pattern)
+                .default_value(DEFAULT_NAME_PATTERN),
+        )
+        .arg(
+            Arg::new("strict")
+                .global(true)
+                .long("strict")
+                .help("Treat validation warnings as errors")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-reachability")
+                .global(true)
+                .long("check-reachability")
+                .help("Warn about clusters whose server does not accept a TCP connection")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("reachability-timeout")
+                .global(true)
+                .long("reachability-timeout")
+                .value_name("DURATION")
+                .help("Time limit for the reachability check")
+                .value_parser(parse_duration)
+                .default_value("2s"),
+        )
+        .arg(
+            Arg::new("audit")
+                .global(true)
+                .long("audit")
+                .help("Report redundancies in the merged config, such as contexts that differ only by namespace or clusters sharing a CA under different server hosts")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("split-output")
+                .long("split-output")
+                .value_name("DIR")
+                .help("Write clusters, contexts, users, and current-context as separate partial kubeconfigs in DIR instead of the output file"),
+        )
+        .arg(
+            Arg::new("output-template")
+                .long("output-template")
+                .value_name("TEMPLATE")
+                .help("Write contexts grouped by --group-pattern to the files TEMPLATE names, such as '{1}-config.yaml', each with the clusters and users they use; unmatched contexts go to the output file")
+                .requires("group-pattern")
+                .conflicts_with_all(["split-output", "encrypt-output"]),
+        )
+        .arg(
+            Arg::new("group-pattern")
+                .long("group-pattern")
+                .value_name("REGEX")
+                .help("Regex searched in context names to group them for --output-template, such as '-(prod|staging|dev)-'")
+                .value_parser(Regex::new)
+                .allow_hyphen_values(true)
+                .requires("output-template"),
+        )
+        .arg(
+            Arg::new("color")
+                .global(true)
+                .long("color")
+                .value_name("WHEN")
+                .help("Color terminal output; auto colors only when stderr is a terminal and NO_COLOR is unset")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("summary-format")
+                .long("summary-format")
+                .value_name("FORMAT")
+                .help("Format of the final result line printed to stderr")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("aliases-file")
+                .long("aliases-file")
+                .value_name("FILE")
+                .help("Write a YAML map from each renamed context's original name to its new name, for use as short aliases"),
+        )
+        .arg(
+            Arg::new("merge-report-file")
+                .long("merge-report-file")
+                .value_name("FILE")
+                .help("Append a JSON line with the time and stats of every run to FILE"),
+        )
+        .arg(
+            Arg::new("status-file")
+                .long("status-file")
+                .value_name("FILE")
+                .help("Overwrite FILE with the time and stats of the latest run as JSON"),
+        )
+        .arg(
+            Arg::new("parse-only")
+                .long("parse-only")
+                .help("Only read and parse every input file, reporting per-file parse times and failures; nothing is merged or written")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("Print every merge decision to stderr, regardless of log level")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report-format")
+                .long("report-format")
+                .value_name("FORMAT")
+                .help("Format of the merge report: text logs skipped entries, json prints them to stdout, markdown prints a full report with sources and issues to stdout")
+                .value_parser(["text", "json", "markdown"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .value_name("TEMPLATE")
+                .help("Prepend a comment header to the output ({timestamp} and {files} are substituted)")
+                .num_args(0..=1)
+                .default_missing_value(DEFAULT_HEADER)
+                .overrides_with("no-header"),
+        )
+        .arg(
+            Arg::new("no-header")
+                .long("no-header")
+                .help("Do not write a comment header")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("header"),
+        )
+        .arg(
+            Arg::new("canonical")
+                .long("canonical")
+                .help("Write canonical output: entries sorted by name and all keys sorted")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("stable-order")
+                .long("stable-order")
+                .help("Order entries by name, so the same inputs give byte-identical output whatever order they were read in")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("document-start")
+                .long("document-start")
+                .help("Begin the output with a '---' document start marker")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .value_name("FORMAT")
+                .help("Print how the output would change instead of writing it: changed entries, or a unified diff")
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_parser(["entries", "unified"])
+                .default_missing_value("entries"),
+        )
+        .arg(
+            Arg::new("encrypt-output")
+                .long("encrypt-output")
+                .value_name("age:RECIPIENT")
+                .help("Encrypt the output as an armored age file to RECIPIENT (an age1... public key); repeat for several recipients")
+                .value_parser(parse_age_recipient)
+                .conflicts_with_all(["diff", "split-output"])
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("post-hook")
+                .long("post-hook")
+                .value_name("CMD")
+                .help("Run CMD through the shell after writing the output, with KUBEMERGE_OUTPUT set to its path"),
+        )
+        .arg(
+            Arg::new("post-hook-required")
+                .long("post-hook-required")
+                .help("Fail when the post-hook exits unsuccessfully")
+                .requires("post-hook")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Do everything except writing the output, and report what would be written")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Back up and rewrite the output even when its content would not change")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("touch-only-on-change")
+                .long("touch-only-on-change")
+                .help("Guarantee the output is not rewritten, and its mtime not updated, when its content would not change, by rejecting options that always rewrite it")
+                .conflicts_with_all(["force", "encrypt-output", "split-output"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help("Re-read the written output and restore the backup if it does not match the merge result")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Merge the input files in memory and report validation issues")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Report format")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check an existing kubeconfig for common problems")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .help("Kubeconfig to check (defaults to the output file)"),
+                ),
+        )
+        .subcommand(
+            Command::new("describe")
+                .about("Show where a context connects and how it authenticates, without secrets")
+                .arg(
+                    Arg::new("context")
+                        .value_name("CONTEXT")
+                        .required(true)
+                        .help("Name of the context to describe"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_name("FILE")
+                        .help("Kubeconfig to read (defaults to the output file)"),
+                ),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Restore the most recent backup of the output file, reverting the last merge"),
+        )
+        .subcommand(
+            Command::new("list-backups")
+                .about("List the backups of the output file, newest first")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print the backups as JSON")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("split")
+                .about("Merge the input files and write one self-contained kubeconfig per context or cluster")
+                .arg(
+                    Arg::new("dir")
+                        .short('d')
+                        .long("dir")
+                        .value_name("DIR")
+                        .help("Directory to write the split kubeconfigs to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("split-by")
+                        .long("split-by")
+                        .value_name("ENTITY")
+                        .help("Write one file per context or per cluster")
+                        .value_parser(["context", "cluster"])
+                        .default_value("context"),
+                ),
+        )
+}
+
+/// Anchors the pattern so it has to match the whole name.
+fn parse_name_pattern(value: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{})$", value)).map_err(|e| e.to_string())
+}
+
+fn default_config_path(home_dir: &str) -> String {
+    match std::env::var("XDG_CONFIG_HOME") {
+        Ok(config_home) if !config_home.is_empty() => {
+            format!("{}/kubemerge/config.yaml", config_home)
+        }
+        _ => format!("{}/.config/kubemerge/config.yaml", home_dir),
+    }
+}
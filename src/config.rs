@@ -1,7 +1,16 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_yml::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The kubeconfig `apiVersion` kubectl reads and writes.
+pub const API_VERSION: &str = "v1";
+
+/// A kubeconfig file. This and the entry structs are `#[non_exhaustive]` so
+/// that newly modelled fields are not breaking changes; outside this crate,
+/// build them with their constructors and set fields afterwards.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct KubeConfig {
     #[serde(rename = "apiVersion")]
     pub api_version: String,
@@ -12,19 +21,210 @@ pub struct KubeConfig {
     pub contexts: Option<Vec<NamedContext>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub users: Option<Vec<NamedUser>>,
-    #[serde(rename = "current-context", skip_serializing_if = "String::is_empty")]
+    /// This and `preferences` are omitted from the output when empty, as
+    /// with `--clear-current-context`, so they default to empty when read
+    /// back.
+    #[serde(
+        rename = "current-context",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
     pub current_context: String,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
-    pub preferences: HashMap<String, serde_yml::Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub preferences: BTreeMap<String, serde_yml::Value>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+impl KubeConfig {
+    /// An empty `v1` config.
+    pub fn new() -> Self {
+        Self {
+            api_version: API_VERSION.to_string(),
+            kind: "Config".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Sorts clusters, contexts, and users by name.
+    pub fn sort_entries(&mut self) {
+        if let Some(clusters) = &mut self.clusters {
+            clusters.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        if let Some(contexts) = &mut self.contexts {
+            contexts.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        if let Some(users) = &mut self.users {
+            users.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+
+    /// Serializes with every mapping's keys sorted, typed fields included,
+    /// so the layout does not depend on field declaration order. Entry order
+    /// is left alone; see [`KubeConfig::sort_entries`].
+    pub fn to_canonical_yaml(&self) -> Result<String, serde_yml::Error> {
+        let mut value = serde_yml::to_value(self)?;
+        sort_keys(&mut value);
+        serde_yml::to_string(&value)
+    }
+
+    /// Drops explicit `null`s from untyped fields, so they are omitted on
+    /// output just like unset typed fields. kubectl treats a null and a
+    /// missing key the same; nulls inside lists are kept as they are
+    /// positional.
+    pub fn drop_nulls(&mut self) {
+        retain_non_null(&mut self.preferences);
+        retain_non_null(&mut self.other);
+
+        for cluster in self.clusters.iter_mut().flatten() {
+            retain_non_null(&mut cluster.cluster.other);
+        }
+        for context in self.contexts.iter_mut().flatten() {
+            retain_non_null(&mut context.context.other);
+        }
+        for user in self.users.iter_mut().flatten() {
+            retain_non_null(&mut user.user.other);
+            if let Some(provider) = &mut user.user.auth_provider {
+                retain_non_null(&mut provider.config);
+                retain_non_null(&mut provider.other);
+            }
+            if let Some(exec) = &mut user.user.exec {
+                retain_non_null(&mut exec.other);
+            }
+        }
+    }
+
+    /// Trims leading and trailing whitespace from names, references, server
+    /// URLs, tokens, and file paths. Returns a description of every field
+    /// that changed, such as `cluster 'prod' server`.
+    pub fn trim_whitespace(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        let mut trim = |value: &mut String, field: String| {
+            let trimmed = value.trim();
+            if trimmed.len() != value.len() {
+                *value = trimmed.to_string();
+                changed.push(field);
+            }
+        };
+
+        trim(&mut self.current_context, "current-context".to_string());
+        for entry in self.clusters.iter_mut().flatten() {
+            let name = entry.name.trim().to_string();
+            trim(&mut entry.name, format!("cluster '{}' name", name));
+            let cluster = &mut entry.cluster;
+            trim(&mut cluster.server, format!("cluster '{}' server", name));
+            let optional = [
+                (&mut cluster.tls_server_name, "tls-server-name"),
+                (&mut cluster.certificate_authority, "certificate-authority"),
+            ];
+            for (value, field) in optional {
+                if let Some(value) = value {
+                    trim(value, format!("cluster '{}' {}", name, field));
+                }
+            }
+        }
+        for entry in self.contexts.iter_mut().flatten() {
+            let name = entry.name.trim().to_string();
+            trim(&mut entry.name, format!("context '{}' name", name));
+            let context = &mut entry.context;
+            trim(&mut context.cluster, format!("context '{}' cluster", name));
+            trim(&mut context.user, format!("context '{}' user", name));
+            if let Some(namespace) = &mut context.namespace {
+                trim(namespace, format!("context '{}' namespace", name));
+            }
+        }
+        for entry in self.users.iter_mut().flatten() {
+            let name = entry.name.trim().to_string();
+            trim(&mut entry.name, format!("user '{}' name", name));
+            let user = &mut entry.user;
+            let optional = [
+                (&mut user.token, "token"),
+                (&mut user.token_file, "tokenFile"),
+                (&mut user.client_certificate, "client-certificate"),
+                (&mut user.client_key, "client-key"),
+            ];
+            for (value, field) in optional {
+                if let Some(value) = value {
+                    trim(value, format!("user '{}' {}", name, field));
+                }
+            }
+        }
+        changed
+    }
+
+    /// Sorts cluster, context, and user extensions by name and drops same-named
+    /// extensions, keeping the last one as kubectl does.
+    pub fn normalize_extensions(&mut self) {
+        for cluster in self.clusters.iter_mut().flatten() {
+            normalize_extensions(&mut cluster.cluster.extensions);
+        }
+        for context in self.contexts.iter_mut().flatten() {
+            normalize_extensions(&mut context.context.extensions);
+        }
+        for user in self.users.iter_mut().flatten() {
+            normalize_extensions(&mut user.user.extensions);
+        }
+    }
+}
+
+fn normalize_extensions(extensions: &mut Option<Vec<NamedExtension>>) {
+    if let Some(list) = extensions.take() {
+        let by_name: BTreeMap<String, Value> = list
+            .into_iter()
+            .map(|extension| (extension.name, extension.extension))
+            .collect();
+        *extensions = Some(
+            by_name
+                .into_iter()
+                .map(|(name, extension)| NamedExtension { name, extension })
+                .collect(),
+        );
+    }
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            let mut entries: Vec<(Value, Value)> = std::mem::take(mapping).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            for (key, mut child) in entries {
+                sort_keys(&mut child);
+                mapping.insert(key, child);
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(sort_keys),
+        Value::Tagged(tagged) => sort_keys(&mut tagged.value),
+        _ => {}
+    }
+}
+
+fn retain_non_null(map: &mut BTreeMap<String, Value>) {
+    drop_null_entries(map.values_mut());
+    map.retain(|_, value| !value.is_null());
+}
+
+fn drop_null_entries<'a>(values: impl Iterator<Item = &'a mut Value>) {
+    for value in values {
+        match value {
+            Value::Mapping(mapping) => {
+                drop_null_entries(mapping.values_mut());
+                mapping.retain(|_, value| !value.is_null());
+            }
+            Value::Sequence(items) => drop_null_entries(items.iter_mut()),
+            Value::Tagged(tagged) => drop_null_entries(std::iter::once(&mut tagged.value)),
+            _ => {}
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct NamedCluster {
     pub name: String,
     pub cluster: Cluster,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct Cluster {
     #[serde(
         rename = "certificate-authority-data",
@@ -37,38 +237,83 @@ pub struct Cluster {
     )]
     pub certificate_authority: Option<String>,
     pub server: String,
+    /// Server name to use for SNI and certificate verification, for
+    /// clusters reached through a shared address.
+    #[serde(rename = "tls-server-name", skip_serializing_if = "Option::is_none")]
+    pub tls_server_name: Option<String>,
     #[serde(
         rename = "insecure-skip-tls-verify",
         skip_serializing_if = "Option::is_none"
     )]
     pub insecure_skip_tls_verify: Option<bool>,
+    #[serde(
+        rename = "disable-compression",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub disable_compression: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<NamedExtension>>,
     #[serde(flatten)]
-    pub other: HashMap<String, serde_yml::Value>,
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+impl Cluster {
+    /// A cluster at `server` with everything else unset.
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            server: server.into(),
+            ..Self::default()
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct NamedContext {
     pub name: String,
     pub context: Context,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct Context {
     pub cluster: String,
     pub user: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<NamedExtension>>,
     #[serde(flatten)]
-    pub other: HashMap<String, serde_yml::Value>,
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+impl Context {
+    /// A context using `cluster` and `user`, without a namespace.
+    pub fn new(cluster: impl Into<String>, user: impl Into<String>) -> Self {
+        Self {
+            cluster: cluster.into(),
+            user: user.into(),
+            ..Self::default()
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct NamedExtension {
+    pub name: String,
+    #[serde(default)]
+    pub extension: serde_yml::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct NamedUser {
     pub name: String,
     pub user: User,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A user's credentials. Every field is optional, so start from
+/// `User::default()`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct User {
     #[serde(
         rename = "client-certificate-data",
@@ -83,10 +328,70 @@ pub struct User {
     pub client_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    #[serde(rename = "tokenFile", skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub username: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
+    pub impersonate: Option<String>,
+    #[serde(rename = "as-uid", skip_serializing_if = "Option::is_none")]
+    pub impersonate_uid: Option<String>,
+    #[serde(rename = "as-groups", skip_serializing_if = "Option::is_none")]
+    pub impersonate_groups: Option<Vec<String>>,
+    #[serde(rename = "as-user-extra", skip_serializing_if = "Option::is_none")]
+    pub impersonate_user_extra: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(rename = "auth-provider", skip_serializing_if = "Option::is_none")]
+    pub auth_provider: Option<AuthProvider>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec: Option<ExecConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<NamedExtension>>,
+    /// Fields kubemerge does not model, such as vendor-specific keys for
+    /// PKCS#12 bundles. They are kept verbatim, written back in key order,
+    /// and count when comparing users.
     #[serde(flatten)]
-    pub other: HashMap<String, serde_yml::Value>,
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+/// A legacy authentication provider such as `oidc`, `gcp`, or `azure`. Its
+/// `config` holds the provider's settings along with cached tokens and
+/// their expiry.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct AuthProvider {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config: BTreeMap<String, serde_yml::Value>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+/// A credential plugin kubectl runs to fetch a token or certificate.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ExecConfig {
+    #[serde(rename = "apiVersion", skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<ExecEnvVar>>,
+    #[serde(rename = "installHint", skip_serializing_if = "Option::is_none")]
+    pub install_hint: Option<String>,
+    #[serde(rename = "provideClusterInfo", skip_serializing_if = "Option::is_none")]
+    pub provide_cluster_info: Option<bool>,
+    #[serde(rename = "interactiveMode", skip_serializing_if = "Option::is_none")]
+    pub interactive_mode: Option<String>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_yml::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct ExecEnvVar {
+    pub name: String,
+    pub value: String,
 }
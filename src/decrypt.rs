@@ -0,0 +1,138 @@
+use age::armor::{ArmoredWriter, Format};
+use std::env;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1";
+const AGE_ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Environment variables naming an age identity file, in lookup order.
+const AGE_IDENTITY_VARS: [&str; 2] = ["KUBEMERGE_AGE_IDENTITY", "SOPS_AGE_KEY_FILE"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    Age,
+    Sops,
+}
+
+/// Recognizes age files (binary or armored) by their header and sops files
+/// by their top-level `sops` metadata block. Every input is checked, so the
+/// document is only parsed when a line starts with a `sops` key.
+pub fn detect_encryption(data: &[u8]) -> Option<Encryption> {
+    if data.starts_with(AGE_HEADER) {
+        return Some(Encryption::Age);
+    }
+
+    let text = std::str::from_utf8(data).ok()?;
+    if text.trim_start().starts_with(AGE_ARMOR_HEADER) {
+        return Some(Encryption::Age);
+    }
+
+    // sops writes its metadata last, at the top level in YAML and as an
+    // indented key in JSON.
+    let has_sops_key = text
+        .lines()
+        .any(|line| line.starts_with("sops:") || line.trim_start().starts_with("\"sops\":"));
+    if !has_sops_key {
+        return None;
+    }
+    let document: serde_yml::Mapping = serde_yml::from_str(text).ok()?;
+    document
+        .get("sops")
+        .and_then(|metadata| metadata.get("mac"))
+        .map(|_| Encryption::Sops)
+}
+
+/// Decrypts a file in memory. age files are decrypted with the identity file
+/// named by `KUBEMERGE_AGE_IDENTITY` or `SOPS_AGE_KEY_FILE`; sops files are
+/// handed to the `sops` binary, which finds its own keys.
+pub fn decrypt(path: &Path, data: &[u8], encryption: Encryption) -> Result<String, String> {
+    match encryption {
+        Encryption::Age => decrypt_age(data),
+        Encryption::Sops => decrypt_sops(path),
+    }
+}
+
+fn decrypt_age(data: &[u8]) -> Result<String, String> {
+    let (var, identity_file) = AGE_IDENTITY_VARS
+        .iter()
+        .find_map(|var| env::var(var).ok().map(|value| (*var, value)))
+        .ok_or_else(|| {
+            format!(
+                "no age identity configured, set {}",
+                AGE_IDENTITY_VARS.join(" or ")
+            )
+        })?;
+    debug!("Using age identity from {} ({})", var, identity_file);
+
+    let identities = age::IdentityFile::from_file(identity_file.clone())
+        .map_err(|e| format!("failed to read identity {}: {}", identity_file, e))?
+        .into_identities()
+        .map_err(|e| format!("failed to load identity {}: {}", identity_file, e))?;
+
+    let decryptor =
+        age::Decryptor::new(age::armor::ArmoredReader::new(data)).map_err(|e| e.to_string())?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref()))
+        .map_err(|e| e.to_string())?;
+
+    let mut plaintext = String::new();
+    reader
+        .read_to_string(&mut plaintext)
+        .map_err(|e| e.to_string())?;
+    Ok(plaintext)
+}
+
+/// Parses an `--encrypt-output` target of the form `age:RECIPIENT`, where
+/// the recipient is an age public key (`age1...`).
+pub fn parse_age_recipient(value: &str) -> Result<age::x25519::Recipient, String> {
+    let recipient = value
+        .strip_prefix("age:")
+        .ok_or_else(|| format!("expected age:RECIPIENT, got '{}'", value))?;
+    recipient
+        .parse()
+        .map_err(|e| format!("invalid age recipient '{}': {}", recipient, e))
+}
+
+/// Encrypts `plaintext` to all `recipients` as an armored age file, which
+/// stays text and diffs sensibly in a repository.
+pub fn encrypt_age(
+    plaintext: &str,
+    recipients: &[age::x25519::Recipient],
+) -> Result<String, String> {
+    let encryptor =
+        age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(|e| e.to_string())?;
+
+    let mut ciphertext = Vec::new();
+    let armored = ArmoredWriter::wrap_output(&mut ciphertext, Format::AsciiArmor)
+        .map_err(|e| e.to_string())?;
+    let mut writer = encryptor.wrap_output(armored).map_err(|e| e.to_string())?;
+    writer
+        .write_all(plaintext.as_bytes())
+        .map_err(|e| e.to_string())?;
+    writer
+        .finish()
+        .and_then(|armored| armored.finish())
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(ciphertext).map_err(|e| e.to_string())
+}
+
+fn decrypt_sops(path: &Path) -> Result<String, String> {
+    let output = Command::new("sops")
+        .arg("--decrypt")
+        .arg(path)
+        .output()
+        .map_err(|e| format!("failed to run sops: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "sops exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|e| e.to_string())
+}
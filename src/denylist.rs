@@ -0,0 +1,114 @@
+use crate::config::KubeConfig;
+use crate::merge::{EntryKind, SkipReason, SkippedEntry};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{error, warn};
+
+const SHA256_PREFIX: &str = "sha256:";
+
+/// Tokens that must not appear in a merged config, kept as SHA-256 hex
+/// digests so plaintext entries are never held longer than needed.
+#[derive(Debug, Default)]
+pub struct TokenDenylist {
+    digests: HashSet<String>,
+}
+
+impl TokenDenylist {
+    /// Reads a denylist file: one token per line, either as `sha256:<hex>`
+    /// or in plaintext. Blank lines and `#` comments are ignored.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read token denylist {}: {}", path.display(), e))?;
+        let mut digests = HashSet::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digest = match line.strip_prefix(SHA256_PREFIX) {
+                Some(hex) if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    hex.to_ascii_lowercase()
+                }
+                Some(_) => {
+                    return Err(format!(
+                        "{}:{}: expected a SHA-256 hex digest after '{}'",
+                        path.display(),
+                        number + 1,
+                        SHA256_PREFIX
+                    )
+                    .into());
+                }
+                None => token_digest(line),
+            };
+            digests.insert(digest);
+        }
+        Ok(Self { digests })
+    }
+
+    pub fn contains(&self, token: &str) -> bool {
+        self.digests.contains(&token_digest(token.trim()))
+    }
+}
+
+/// Returns the lowercase hex SHA-256 digest of a token, the form denylist
+/// entries are shared in.
+pub fn token_digest(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Drops the users of one input whose token is on the denylist and returns
+/// them as skipped entries, or fails on the first one when `strict` is set.
+/// Inputs are scrubbed before duplicates are resolved, so a clean definition
+/// of the same user in a later file is kept. Tokens are never logged.
+pub fn scrub_denied_tokens(
+    config: &mut KubeConfig,
+    source: &Path,
+    denylist: &TokenDenylist,
+    strict: bool,
+) -> Result<Vec<SkippedEntry>, Box<dyn std::error::Error>> {
+    let Some(users) = &mut config.users else {
+        return Ok(Vec::new());
+    };
+    let mut skipped = Vec::new();
+    let mut denied = None;
+    users.retain(|user| {
+        let is_denied = user
+            .user
+            .token
+            .as_deref()
+            .is_some_and(|t| denylist.contains(t));
+        if is_denied {
+            denied.get_or_insert_with(|| user.name.clone());
+            skipped.push(SkippedEntry {
+                kind: Some(EntryKind::User),
+                name: user.name.clone(),
+                source_file: source.to_path_buf(),
+                reason: SkipReason::DeniedToken,
+            });
+        }
+        !is_denied
+    });
+
+    if strict && let Some(name) = denied {
+        let message = format!(
+            "User '{}' from {} uses a denylisted token",
+            name,
+            source.display()
+        );
+        error!("{}", message);
+        return Err(message.into());
+    }
+    for entry in &skipped {
+        warn!(
+            "Dropping user '{}' from {}, its token is on the denylist",
+            entry.name,
+            source.display()
+        );
+    }
+    Ok(skipped)
+}
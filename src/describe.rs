@@ -0,0 +1,81 @@
+use crate::config::KubeConfig;
+use crate::validate::auth_methods;
+use std::fmt::Write;
+
+/// Renders a context together with the cluster and user it refers to:
+/// where it connects and how it authenticates, without any credentials.
+/// Returns `None` when the config has no such context.
+pub fn describe_context(config: &KubeConfig, name: &str) -> Option<String> {
+    let context = config.contexts.iter().flatten().find(|c| c.name == name)?;
+    let cluster = config
+        .clusters
+        .iter()
+        .flatten()
+        .find(|c| c.name == context.context.cluster);
+    let user = config
+        .users
+        .iter()
+        .flatten()
+        .find(|u| u.name == context.context.user);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "Context:    {}", context.name);
+    if name == config.current_context {
+        let _ = writeln!(out, "Current:    yes");
+    }
+    let _ = writeln!(
+        out,
+        "Namespace:  {}",
+        context.context.namespace.as_deref().unwrap_or("default")
+    );
+
+    let _ = writeln!(out, "Cluster:    {}", context.context.cluster);
+    match cluster {
+        Some(cluster) => {
+            let cluster = &cluster.cluster;
+            let _ = writeln!(out, "  Server:   {}", cluster.server);
+            if let Some(server_name) = &cluster.tls_server_name {
+                let _ = writeln!(out, "  SNI:      {}", server_name);
+            }
+            let ca = match (
+                &cluster.certificate_authority_data,
+                &cluster.certificate_authority,
+            ) {
+                (Some(_), _) => "embedded".to_string(),
+                (None, Some(path)) => format!("file {}", path),
+                (None, None) => "system roots".to_string(),
+            };
+            let _ = writeln!(out, "  CA:       {}", ca);
+            if cluster.insecure_skip_tls_verify == Some(true) {
+                let _ = writeln!(out, "  TLS:      verification disabled");
+            }
+        }
+        None => {
+            let _ = writeln!(out, "  (not found)");
+        }
+    }
+
+    let _ = writeln!(out, "User:       {}", context.context.user);
+    match user {
+        Some(user) => {
+            let methods = auth_methods(&user.user);
+            let auth = if methods.is_empty() {
+                "none".to_string()
+            } else {
+                methods.join(", ")
+            };
+            let _ = writeln!(out, "  Auth:     {}", auth);
+        }
+        None => {
+            let _ = writeln!(out, "  (not found)");
+        }
+    }
+
+    let resolved = if cluster.is_some() && user.is_some() {
+        "all resolve"
+    } else {
+        "dangling"
+    };
+    let _ = writeln!(out, "References: {}", resolved);
+    Some(out)
+}
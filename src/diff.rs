@@ -0,0 +1,66 @@
+use crate::config::KubeConfig;
+use crate::merge::EntryKind;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+
+/// Lists entries added (`+`), removed (`-`), and changed (`~`) between two
+/// configs, plus a changed current-context.
+pub fn entry_diff(old: &KubeConfig, new: &KubeConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    diff_entries(
+        EntryKind::Cluster,
+        old.clusters.iter().flatten().map(|c| (&c.name, &c.cluster)),
+        new.clusters.iter().flatten().map(|c| (&c.name, &c.cluster)),
+        &mut lines,
+    );
+    diff_entries(
+        EntryKind::Context,
+        old.contexts.iter().flatten().map(|c| (&c.name, &c.context)),
+        new.contexts.iter().flatten().map(|c| (&c.name, &c.context)),
+        &mut lines,
+    );
+    diff_entries(
+        EntryKind::User,
+        old.users.iter().flatten().map(|u| (&u.name, &u.user)),
+        new.users.iter().flatten().map(|u| (&u.name, &u.user)),
+        &mut lines,
+    );
+
+    if old.current_context != new.current_context {
+        lines.push(format!(
+            "~ current-context '{}' -> '{}'",
+            old.current_context, new.current_context
+        ));
+    }
+    lines
+}
+
+fn diff_entries<'a, T: PartialEq + 'a>(
+    kind: EntryKind,
+    old: impl Iterator<Item = (&'a String, &'a T)>,
+    new: impl Iterator<Item = (&'a String, &'a T)>,
+    lines: &mut Vec<String>,
+) {
+    let old: BTreeMap<_, _> = old.collect();
+    let new: BTreeMap<_, _> = new.collect();
+
+    for (name, entry) in &new {
+        match old.get(name) {
+            None => lines.push(format!("+ {} '{}'", kind, name)),
+            Some(previous) if previous != entry => lines.push(format!("~ {} '{}'", kind, name)),
+            Some(_) => {}
+        }
+    }
+    for name in old.keys().filter(|name| !new.contains_key(*name)) {
+        lines.push(format!("- {} '{}'", kind, name));
+    }
+}
+
+/// Renders a `diff -u` style patch between the old and new file contents.
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(path, path)
+        .to_string()
+}
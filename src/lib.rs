@@ -0,0 +1,25 @@
+pub mod archive;
+pub mod audit;
+pub mod certs;
+pub mod config;
+pub mod decrypt;
+pub mod denylist;
+pub mod describe;
+pub mod diff;
+pub mod merge;
+pub mod metadata;
+pub mod pin;
+pub mod profile;
+pub mod reachability;
+pub mod rename;
+pub mod report;
+pub mod schema;
+pub mod secret;
+pub mod select;
+pub mod split;
+pub mod style;
+pub mod sync;
+pub mod token;
+pub mod transform;
+pub mod utils;
+pub mod validate;
@@ -1,106 +1,1044 @@
-use clap::{Arg, Command};
+use chrono::{Local, TimeDelta};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use regex::Regex;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
-use tracing::{debug, error, info};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-mod config;
-mod merge;
-mod utils;
+mod cli;
 
-use merge::merge_kubeconfigs;
-use utils::{create_backup, find_yaml_files, print_summary};
+use cli::build_cli;
+use kubemerge::archive::{is_archive, read_archive};
+use kubemerge::audit::audit_config;
+use kubemerge::config::KubeConfig;
+use kubemerge::decrypt::encrypt_age;
+use kubemerge::denylist::TokenDenylist;
+use kubemerge::describe::describe_context;
+use kubemerge::diff::{entry_diff, unified_diff};
+use kubemerge::merge::{
+    EntryKind, InputFormat, MergeOptions, NamespaceConflict, SkipReason, SkippedEntry,
+    load_kubeconfig, merge_configs, merge_contents, merge_kubeconfigs, parse_kubeconfig,
+};
+use kubemerge::metadata::{apply_metadata, load_metadata};
+use kubemerge::pin::{apply_pins, load_pins};
+use kubemerge::profile::load_profile;
+use kubemerge::reachability::check_reachability;
+use kubemerge::rename::{
+    apply_renames, coalesce_clusters, context_aliases, prefix_renames, server_suffix_renames,
+    template_renames,
+};
+use kubemerge::report::{
+    MergeStats, append_merge_report, print_skipped, print_summary_line, render_markdown,
+    write_status_file,
+};
+use kubemerge::schema::{check_schema, load_schema};
+use kubemerge::select::{
+    Selector, allow_servers, drop_expired_users, load_allowed_servers, select_entries,
+};
+use kubemerge::split::{
+    check_group_template, minify, partition_by_group, split_config, split_sections, write_parts,
+};
+use kubemerge::style::{self, color_enabled};
+use kubemerge::sync::{SyncState, reconcile};
+use kubemerge::transform::apply_transform_script;
+use kubemerge::utils::{
+    ScanOptions, apply_priority, create_backup, expand_input_pattern, expand_output_path,
+    find_yaml_files, format_age, format_size, is_input_pattern, is_unchanged, list_backups,
+    parse_output_fd, print_summary, read_file_list, render_header, restore_backup, run_post_hook,
+    same_file, verify_output, warn_if_exposed, write_to_fd,
+};
+use kubemerge::validate::{
+    Severity, ValidateOptions, doctor_checks, has_errors, log_issues, print_categorized,
+    print_report, validate_config,
+};
 
-fn init_tracing() {
+fn init_tracing(color: bool) {
     let subscriber = FmtSubscriber::builder()
         .with_env_filter(
             EnvFilter::from_default_env().add_directive("kubemerge=info".parse().unwrap()),
         )
+        .with_writer(std::io::stderr)
+        .with_ansi(color)
         .finish();
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    init_tracing();
-
     let home_dir = env::var("HOME")
         .or_else(|_| env::var("USERPROFILE"))
         .map_err(|_| "HOME or USERPROFILE environment variable not found")?;
 
-    let matches = Command::new("kubemerge")
-        .version("0.2.0")
-        .about("Merges multiple kubeconfig YAML files into a single file")
-        .arg(
-            Arg::new("input")
-                .short('i')
-                .long("input")
-                .value_name("DIR")
-                .help("Input directory containing kubeconfig files")
-                .default_value(format!("{}/.kube", home_dir)),
-        )
-        .arg(
-            Arg::new("output")
-                .short('o')
-                .long("output")
-                .value_name("FILE")
-                .help("Output file path")
-                .default_value(format!("{}/.kube/config", home_dir)),
-        )
-        .arg(
-            Arg::new("exclude")
-                .short('e')
-                .long("exclude")
-                .value_name("PATTERN")
-                .help("Exclude files matching pattern")
-                .action(clap::ArgAction::Append),
-        )
-        .get_matches();
+    let args: Vec<OsString> = env::args_os().collect();
+    let mut matches = build_cli(&home_dir).get_matches_from(&args);
+    // A profile that fails to load fails the run like any merge error, so
+    // the summary and status file still report it.
+    let mut profile_error = None;
+    if let Some(name) = matches.get_one::<String>("profile") {
+        let config_file = matches.get_one::<String>("config").unwrap();
+        let given: Vec<&str> = matches
+            .ids()
+            .map(|id| id.as_str())
+            .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+            .collect();
+        match load_profile(config_file, name, &given) {
+            Ok(profile_args) => {
+                matches = build_cli(&home_dir).get_matches_from(
+                    args.iter()
+                        .take(1)
+                        .cloned()
+                        .chain(profile_args)
+                        .chain(args.iter().skip(1).cloned()),
+                );
+            }
+            Err(e) => profile_error = Some(e),
+        }
+    }
 
-    let input_dir = matches.get_one::<String>("input").unwrap();
-    let output_file = matches.get_one::<String>("output").unwrap();
-    let exclude_patterns: Vec<&String> = matches.get_many("exclude").unwrap_or_default().collect();
+    // Tracing starts only now so that --color, which a profile may set,
+    // applies to every message.
+    let color = color_enabled(matches.get_one::<String>("color").unwrap());
+    style::set_color(color);
+    init_tracing(color);
+    if let Some(name) = matches.get_one::<String>("profile")
+        && profile_error.is_none()
+    {
+        info!(
+            "Using profile '{}' from {}",
+            name,
+            matches.get_one::<String>("config").unwrap()
+        );
+    }
 
-    debug!("Input directory: {}", input_dir);
-    debug!("Output file: {}", output_file);
-    debug!("Exclude patterns: {:?}", exclude_patterns);
+    let mut stats = MergeStats::default();
+    let result = match profile_error {
+        Some(e) => Err(e),
+        None => run(&matches, &mut stats),
+    };
+
+    if matches.subcommand().is_none() {
+        if let Err(e) = &result {
+            stats.fail(e.to_string());
+        }
+        let format = matches.get_one::<String>("summary-format").unwrap();
+        print_summary_line(&stats, format)?;
+        if let Some(path) = matches.get_one::<String>("merge-report-file")
+            && let Err(e) = append_merge_report(Path::new(path), &stats)
+        {
+            warn!("Failed to append the merge report to {}: {}", path, e);
+        }
+        if let Some(path) = matches.get_one::<String>("status-file")
+            && let Err(e) = write_status_file(Path::new(path), &stats)
+        {
+            warn!("Failed to write the status file {}: {}", path, e);
+        }
+    }
+
+    result
+}
 
-    if !Path::new(input_dir).is_dir() {
-        error!("Input directory does not exist: {}", input_dir);
-        return Err(format!("Input directory does not exist: {}", input_dir).into());
+fn run(matches: &ArgMatches, stats: &mut MergeStats) -> Result<(), Box<dyn std::error::Error>> {
+    let input_dir = matches.get_one::<String>("input").unwrap();
+    let output_file = &expand_output_path(matches.get_one::<String>("output").unwrap())?;
+    let output_fd = parse_output_fd(output_file)?;
+    if matches.get_flag("touch-only-on-change") && output_fd.is_some() {
+        return Err(
+            "--touch-only-on-change needs an output file to compare against, not a file descriptor"
+                .into(),
+        );
+    }
+    if matches.contains_id("encrypt-output") && matches.subcommand_matches("split").is_some() {
+        return Err(
+            "--encrypt-output cannot be combined with split, which writes plaintext files".into(),
+        );
     }
+    let scan_options = ScanOptions {
+        exclude_patterns: matches
+            .get_many::<String>("exclude")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        extensions: matches
+            .get_many::<String>("extensions")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        include_extensionless: matches.get_flag("include-extensionless"),
+        strict_io: matches.get_flag("strict-io"),
+    };
+    let files_from = matches.get_one::<String>("files-from");
 
-    if Path::new(output_file).exists() {
-        create_backup(output_file)?;
+    if let Some(doctor_matches) = matches.subcommand_matches("doctor") {
+        let file = doctor_matches
+            .get_one::<String>("file")
+            .map(String::as_str)
+            .unwrap_or(output_file);
+        return run_doctor(Path::new(file));
+    }
+    if let Some(describe_matches) = matches.subcommand_matches("describe") {
+        let file = describe_matches
+            .get_one::<String>("file")
+            .map(String::as_str)
+            .unwrap_or(output_file);
+        let context = describe_matches.get_one::<String>("context").unwrap();
+        return run_describe(Path::new(file), context);
+    }
+    if matches.subcommand_matches("undo").is_some() {
+        return run_undo(output_file);
+    }
+    if let Some(list_matches) = matches.subcommand_matches("list-backups") {
+        return run_list_backups(output_file, list_matches.get_flag("json"));
     }
 
-    if let Some(parent) = Path::new(output_file).parent() {
-        debug!("Creating parent directory: {}", parent.display());
-        fs::create_dir_all(parent)?;
+    debug!("Input directory: {}", input_dir);
+    debug!("Output file: {}", output_file);
+    debug!("Scan options: {:?}", scan_options);
+
+    let input_pattern = is_input_pattern(input_dir);
+    if files_from.is_none() && !input_pattern && !Path::new(input_dir).exists() {
+        error!("Input path does not exist: {}", input_dir);
+        return Err(format!("Input path does not exist: {}", input_dir).into());
     }
 
-    let yaml_files = find_yaml_files(input_dir, &exclude_patterns)?;
+    let mut excluded = Vec::new();
+    let mut archive_members = HashMap::new();
+    let mut yaml_files = match files_from {
+        Some(source) => read_file_list(source)?,
+        None if input_pattern => {
+            let scanned = expand_input_pattern(input_dir, &scan_options)?;
+            excluded = scanned
+                .excluded
+                .into_iter()
+                .map(|file| (file, SkipReason::Excluded))
+                .collect();
+            drop_own_output(scanned.files, output_file, &mut excluded)
+        }
+        None if is_archive(Path::new(input_dir)) => {
+            let contents = read_archive(Path::new(input_dir), &scan_options)?;
+            excluded = contents
+                .excluded
+                .into_iter()
+                .map(|file| (file, SkipReason::Excluded))
+                .collect();
+            let files = contents
+                .members
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect();
+            archive_members.extend(contents.members);
+            files
+        }
+        // An explicitly named file is merged whatever its extension.
+        None if !Path::new(input_dir).is_dir() => vec![PathBuf::from(input_dir)],
+        None => {
+            let scanned = find_yaml_files(input_dir, &scan_options)?;
+            excluded = scanned
+                .excluded
+                .into_iter()
+                .map(|file| (file, SkipReason::Excluded))
+                .collect();
+            drop_own_output(scanned.files, output_file, &mut excluded)
+        }
+    };
+    if let Some(priority_file) = matches.get_one::<String>("priority-file") {
+        yaml_files = apply_priority(yaml_files, &read_file_list(priority_file)?);
+    }
     if yaml_files.is_empty() {
-        error!("No kubeconfig YAML files found in {}", input_dir);
-        return Err(format!("No kubeconfig YAML files found in {}", input_dir).into());
+        let origin = files_from.unwrap_or(input_dir);
+        error!("No kubeconfig YAML files found in {}", origin);
+        return Err(format!("No kubeconfig YAML files found in {}", origin).into());
     }
 
+    stats.files = yaml_files.len();
     info!("Found {} kubeconfig files:", yaml_files.len());
     for file in &yaml_files {
         info!("  - {}", file.display());
     }
 
-    let merged_config = merge_kubeconfigs(&yaml_files)?;
-    let yaml_output = serde_yml::to_string(&merged_config)?;
+    let merge_options = MergeOptions {
+        no_duplicates: matches.get_flag("no-duplicates"),
+        expand_env: matches.get_flag("expand-env"),
+        allow_undefined: matches.get_flag("allow-undefined"),
+        relative_cert_paths: matches.get_flag("relative-cert-paths"),
+        namespace_conflict: match matches
+            .get_one::<String>("namespace-conflict")
+            .unwrap()
+            .as_str()
+        {
+            "specific" => NamespaceConflict::Specific,
+            "error" => NamespaceConflict::Error,
+            _ => NamespaceConflict::First,
+        },
+        read_timeout: matches.get_one::<Duration>("read-timeout").copied(),
+        input_format: match matches.get_one::<String>("input-format").unwrap().as_str() {
+            "yaml" => InputFormat::Yaml,
+            "json" => InputFormat::Json,
+            _ => InputFormat::Auto,
+        },
+        decrypt: matches.get_flag("decrypt"),
+        annotate_source: matches.get_flag("annotate-source"),
+        union_duplicate_contexts: matches.get_flag("union-duplicate-contexts"),
+        deep_merge_preferences: matches.get_flag("deep-merge-preferences"),
+        from_secrets: matches.get_flag("from-secrets"),
+        ignore_preferences: matches.get_flag("ignore-preferences"),
+        case_insensitive_names: matches.get_flag("case-insensitive-names"),
+        trim_whitespace: matches.get_flag("trim-whitespace"),
+        api_version: matches.get_one::<String>("api-version").cloned(),
+        strict_io: matches.get_flag("strict-io"),
+        prefer_fresher_tokens: matches.get_flag("prefer-fresher-tokens"),
+        token_denylist: matches
+            .get_one::<String>("token-denylist")
+            .map(|path| TokenDenylist::load(Path::new(path)))
+            .transpose()?,
+        strict_denylist: matches.get_flag("strict"),
+        primary_file: matches.get_one::<String>("primary-file").map(PathBuf::from),
+    };
+    if matches.get_flag("parse-only") {
+        return run_parse_only(&yaml_files, archive_members, &merge_options);
+    }
+    let mut merged = if archive_members.is_empty() {
+        merge_kubeconfigs(&yaml_files, &merge_options)?
+    } else {
+        let contents = yaml_files
+            .iter()
+            .map(|file| match archive_members.remove(file) {
+                Some(content) => Ok((file.clone(), content)),
+                None => Err(format!("{} is not an archive member", file.display())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        merge_contents(contents, &merge_options)?
+    };
+    for (file, reason) in excluded {
+        merged.skip(SkippedEntry {
+            kind: None,
+            name: file
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            source_file: file,
+            reason,
+        });
+    }
+    if matches.get_flag("drop-expired") {
+        drop_expired_users(&mut merged);
+    }
+    if matches.get_flag("coalesce-clusters") {
+        coalesce_clusters(&mut merged);
+    }
+    if let Some(prefix) = matches.get_one::<String>("prefix") {
+        info!("Prefixing all names with '{}'", prefix);
+        for kind in [EntryKind::Cluster, EntryKind::Context, EntryKind::User] {
+            let renames = prefix_renames(&merged, kind, prefix);
+            apply_renames(&mut merged, kind, &renames);
+        }
+    }
+    if matches.get_flag("context-suffix") {
+        info!("Suffixing context names with their server host");
+        let renames = server_suffix_renames(&merged);
+        apply_renames(&mut merged, EntryKind::Context, &renames);
+    }
+    if let Some(template) = matches.get_one::<String>("context-template") {
+        info!("Naming contexts after '{}'", template);
+        let default = matches.get_one::<String>("template-default").unwrap();
+        let renames = template_renames(&merged, template, default);
+        apply_renames(&mut merged, EntryKind::Context, &renames);
+    }
+    if let Some(path) = matches.get_one::<String>("metadata-file") {
+        apply_metadata(&mut merged.config, &load_metadata(path)?);
+    }
+    if let Some(path) = matches.get_one::<String>("allow-servers") {
+        allow_servers(&mut merged, &load_allowed_servers(Path::new(path))?);
+    }
+    if let Some(selectors) = matches.get_many::<Selector>("select") {
+        let selectors: Vec<Selector> = selectors.cloned().collect();
+        select_entries(&mut merged, &selectors);
+    }
+    if matches.get_flag("minify") {
+        minify(&mut merged);
+    }
+    if let Some(script) = matches.get_one::<String>("transform-script") {
+        apply_transform_script(&mut merged, Path::new(script))?;
+    }
+    let mut pins: Vec<String> = matches
+        .get_many::<String>("pin")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    if let Some(path) = matches.get_one::<String>("pins-file") {
+        pins.extend(load_pins(Path::new(path))?);
+    }
+    if !pins.is_empty() {
+        if output_fd.is_some() {
+            return Err("--pin needs an output file to read pinned entries from".into());
+        }
+        apply_pins(
+            &mut merged,
+            &load_existing_output(output_file, "--pin")?,
+            &pins,
+        );
+    }
+    let sync_state = match matches.get_one::<String>("state-file") {
+        Some(state_file) if matches.get_flag("sync") => {
+            if output_fd.is_some() {
+                return Err(
+                    "--sync needs an output file to reconcile, not a file descriptor".into(),
+                );
+            }
+            let state_file = PathBuf::from(state_file);
+            let existing = load_existing_output(output_file, "--sync")?;
+            let changes = reconcile(&mut merged, &existing, &SyncState::load(&state_file)?);
+            info!(
+                "Sync: {} added, {} removed, {} unmanaged entries kept",
+                changes.added, changes.removed, changes.preserved
+            );
+            Some((SyncState::from_result(&merged), state_file))
+        }
+        _ => None,
+    };
+    if matches.get_flag("clear-current-context") && !merged.config.current_context.is_empty() {
+        info!(
+            "Clearing current-context: {}",
+            merged.config.current_context
+        );
+        merged.config.current_context.clear();
+    }
+    let use_context = matches.get_one::<String>("use-context");
+    if let Some(name) = use_context {
+        if !merged
+            .config
+            .contexts
+            .iter()
+            .flatten()
+            .any(|context| &context.name == name)
+        {
+            error!("Context '{}' not found in the merged config", name);
+            stats.record(&merged.config);
+            return Err(format!("Context '{}' not found in the merged config", name).into());
+        }
+        debug!("Using context '{}'", name);
+        merged.config.current_context = name.clone();
+    }
+
+    let entry_count = merged.config.clusters.as_ref().map_or(0, Vec::len)
+        + merged.config.contexts.as_ref().map_or(0, Vec::len)
+        + merged.config.users.as_ref().map_or(0, Vec::len);
+    if matches.get_flag("fail-if-empty") && entry_count == 0 {
+        error!("Merged config has no clusters, contexts, or users");
+        stats.record(&merged.config);
+        return Err("Merged config has no clusters, contexts, or users".into());
+    }
+    for (option, kind, count) in [
+        (
+            "max-clusters",
+            "clusters",
+            merged.config.clusters.as_ref().map_or(0, Vec::len),
+        ),
+        (
+            "max-contexts",
+            "contexts",
+            merged.config.contexts.as_ref().map_or(0, Vec::len),
+        ),
+        (
+            "max-users",
+            "users",
+            merged.config.users.as_ref().map_or(0, Vec::len),
+        ),
+    ] {
+        if let Some(&limit) = matches.get_one::<usize>(option)
+            && count > limit
+        {
+            error!(
+                "Merged config has {} {}, more than --{} {}",
+                count, kind, option, limit
+            );
+            stats.record(&merged.config);
+            return Err(format!(
+                "Merged config has {} {}, more than --{} {}",
+                count, kind, option, limit
+            )
+            .into());
+        }
+    }
+
+    let validate_options = ValidateOptions {
+        skip_reference_check: matches.get_flag("no-reference-check"),
+        name_pattern: matches
+            .get_flag("validate-names")
+            .then(|| matches.get_one::<Regex>("name-pattern").unwrap().clone()),
+        require_current_context: matches.get_flag("require-current-context"),
+    };
+    let mut issues = validate_config(&merged.config, &merged.sources, &validate_options);
+    if matches.get_flag("check-reachability") {
+        let timeout = *matches.get_one::<Duration>("reachability-timeout").unwrap();
+        info!("Checking cluster reachability (timeout {:?})", timeout);
+        let unreachable = check_reachability(&merged.config, &merged.sources, timeout);
+        stats.unreachable = Some(unreachable.len());
+        issues.extend(unreachable);
+    }
+    if let Some(path) = matches.get_one::<String>("schema") {
+        let validator = load_schema(Path::new(path))?;
+        issues.extend(check_schema(&merged.config, &validator)?);
+    }
+    if matches.get_flag("strict") {
+        for issue in &mut issues {
+            issue.severity = Severity::Error;
+        }
+    }
+    stats.warnings = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Warning)
+        .count();
+
+    if matches.get_flag("audit") {
+        audit_config(&merged.config, &merged.sources);
+    }
+
+    if let Some(validate_matches) = matches.subcommand_matches("validate") {
+        let format = validate_matches.get_one::<String>("format").unwrap();
+        print_report(&issues, format)?;
+        if has_errors(&issues) {
+            return Err("Validation failed".into());
+        }
+        return Ok(());
+    }
+
+    if matches.get_flag("explain") {
+        for decision in &merged.decisions {
+            eprintln!("explain: {}", decision);
+        }
+    }
+    match matches.get_one::<String>("report-format").unwrap().as_str() {
+        "markdown" => print!("{}", render_markdown(&merged, yaml_files.len(), &issues)),
+        format => print_skipped(&merged.skipped, format)?,
+    }
+    log_issues(&issues);
+    if let Some(issue) = issues.iter().find(|i| i.severity == Severity::Error) {
+        return Err(issue.message.clone().into());
+    }
+
+    if let Some(split_matches) = matches.subcommand_matches("split") {
+        let dir = split_matches.get_one::<String>("dir").unwrap();
+        let by_cluster = split_matches.get_one::<String>("split-by").unwrap() == "cluster";
+        let parts = split_config(&merged.config, Path::new(dir), by_cluster);
+        if !preview_parts(matches, &parts)? {
+            write_parts(&parts)?;
+            info!("Split merged config into {} files in {}", parts.len(), dir);
+        }
+        return Ok(());
+    }
+
+    if let Some(dir) = matches.get_one::<String>("split-output") {
+        let parts = split_sections(&merged.config, Path::new(dir));
+        if !preview_parts(matches, &parts)? {
+            write_parts(&parts)?;
+            info!(
+                "Wrote {} partial kubeconfigs to {}, combine them with KUBECONFIG",
+                parts.len(),
+                dir
+            );
+        }
+        print_summary(&merged.config);
+        stats.record(&merged.config);
+        return Ok(());
+    }
+
+    let aliases_file = matches.get_one::<String>("aliases-file");
+    let aliases = context_aliases(&merged);
+    let mut merged_config = merged.config;
+
+    // Contexts the template's pattern matches move to their own files; the
+    // rest is written to the output like any merge.
+    let mut groups = Vec::new();
+    if let Some(template) = matches.get_one::<String>("output-template") {
+        if output_fd.is_some() {
+            return Err(
+                "--output-template writes unmatched contexts to --output, which must be a file"
+                    .into(),
+            );
+        }
+        let pattern = matches.get_one::<Regex>("group-pattern").unwrap();
+        check_group_template(template, pattern)?;
+        let (matched, unmatched) =
+            partition_by_group(&merged_config, pattern, &expand_output_path(template)?);
+        for (path, mut group) in matched {
+            if path == Path::new(output_file) {
+                return Err(format!(
+                    "--output-template names the output file {}, which holds unmatched contexts",
+                    output_file
+                )
+                .into());
+            }
+            let content = render_output(matches, &mut group, yaml_files.len())?;
+            groups.push((path, group, content));
+        }
+        merged_config = unmatched;
+    }
+
+    // Pipes and other special files are written to directly: there is
+    // nothing to back up and re-reading them would block or consume data.
+    let output_path = Path::new(output_file);
+    let special_output = output_path.exists() && !output_path.is_file();
+    if special_output {
+        info!(
+            "Output {} is not a regular file, skipping backup",
+            output_file
+        );
+    }
+
+    let mut yaml_output = render_output(matches, &mut merged_config, yaml_files.len())?;
+    if let Some(format) = matches.get_one::<String>("diff") {
+        for (path, group, content) in &groups {
+            let existing = if path.is_file() {
+                fs::read_to_string(path)?
+            } else {
+                String::new()
+            };
+            print_diff(format, &existing, content, group, &path.to_string_lossy())?;
+        }
+        let existing = if output_path.is_file() {
+            fs::read_to_string(output_path)?
+        } else {
+            String::new()
+        };
+        stats.record(&merged_config);
+        return print_diff(format, &existing, &yaml_output, &merged_config, output_file);
+    }
+    if matches.get_flag("dry-run") {
+        for (path, group, _) in &groups {
+            info!(
+                "Dry run: would write {} contexts to {}",
+                group.contexts.iter().flatten().count(),
+                path.display()
+            );
+        }
+        info!(
+            "Dry run: would merge {} files into {}",
+            yaml_files.len(),
+            output_file
+        );
+        if let Some(name) = use_context {
+            info!("Dry run: would switch to context \"{}\"", name);
+        }
+        print_summary(&merged_config);
+        stats.record(&merged_config);
+        return Ok(());
+    }
+
+    for (path, group, content) in &groups {
+        write_group_file(path, group, content, matches.get_flag("force"))?;
+    }
+    if let Some(aliases_file) = aliases_file {
+        write_aliases(aliases_file, &aliases)?;
+    }
+
+    let recipients: Vec<age::x25519::Recipient> = matches
+        .get_many("encrypt-output")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let encrypted = !recipients.is_empty();
+    if encrypted {
+        yaml_output = encrypt_age(&yaml_output, &recipients)?;
+        debug!(
+            "Encrypted the output to {} age recipients",
+            recipients.len()
+        );
+    }
+
+    // A descriptor has no path to back up, compare against or rename into.
+    if let Some(fd) = output_fd {
+        write_to_fd(fd, &yaml_output)?;
+        info!(
+            "Successfully merged {} files into file descriptor {}",
+            yaml_files.len(),
+            fd
+        );
+        if let Some(name) = use_context {
+            info!("Switched to context \"{}\"", name);
+        }
+        print_summary(&merged_config);
+        stats.record(&merged_config);
+        if let Some(hook) = matches.get_one::<String>("post-hook") {
+            run_post_hook(hook, output_file, matches.get_flag("post-hook-required"))?;
+        }
+        return Ok(());
+    }
+
+    // An unchanged output is neither backed up nor rewritten, so its mtime
+    // stays put and file watchers do not fire.
+    if !matches.get_flag("force")
+        && output_path.is_file()
+        && is_unchanged(output_file, &yaml_output)
+    {
+        info!("No changes, {} is already up to date", output_file);
+        if let Some(name) = use_context {
+            info!("Switched to context \"{}\"", name);
+        }
+        if let Some((state, state_file)) = &sync_state {
+            state.save(state_file)?;
+        }
+        print_summary(&merged_config);
+        stats.record(&merged_config);
+        return Ok(());
+    }
+
+    let backup = if output_path.is_file() {
+        warn_if_exposed(output_file)?;
+        Some(create_backup(output_file)?)
+    } else {
+        None
+    };
+    stats.backup = backup.clone();
+
+    if let Some(parent) = output_path.parent() {
+        debug!("Creating parent directory: {}", parent.display());
+        fs::create_dir_all(parent)?;
+    }
+
     fs::write(output_file, yaml_output)?;
 
+    if matches.get_flag("verify") && special_output {
+        warn!("Cannot verify non-regular output {}", output_file);
+    } else if matches.get_flag("verify") && encrypted {
+        warn!("Cannot verify encrypted output {}", output_file);
+    } else if matches.get_flag("verify")
+        && let Err(e) = verify_output(output_file, &merged_config)
+    {
+        error!("{}", e);
+        restore_backup(backup.as_deref(), output_file)?;
+        return Err(e);
+    }
+
+    if let Some((state, state_file)) = &sync_state {
+        state.save(state_file)?;
+    }
+
     info!(
-        "Successfully merged {} files into {}",
-        yaml_files.len(),
-        output_file
+        "{}",
+        style::success(format!(
+            "Successfully merged {} files into {}",
+            yaml_files.len(),
+            output_file
+        ))
     );
+    if let Some(name) = use_context {
+        info!("Switched to context \"{}\"", name);
+    }
     print_summary(&merged_config);
+    stats.record(&merged_config);
+
+    if let Some(hook) = matches.get_one::<String>("post-hook") {
+        run_post_hook(hook, output_file, matches.get_flag("post-hook-required"))?;
+    }
+
+    Ok(())
+}
+
+/// Handles `--diff` and `--dry-run` for split output: prints how each file
+/// would change, or logs the files that would be written. Returns whether
+/// the files should be left alone.
+fn preview_parts(
+    matches: &ArgMatches,
+    parts: &[(PathBuf, KubeConfig)],
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if let Some(format) = matches.get_one::<String>("diff") {
+        for (path, part) in parts {
+            let existing = if path.is_file() {
+                fs::read_to_string(path)?
+            } else {
+                String::new()
+            };
+            let content = serde_yml::to_string(part)?;
+            print_diff(format, &existing, &content, part, &path.to_string_lossy())?;
+        }
+        return Ok(true);
+    }
+    if matches.get_flag("dry-run") {
+        for (path, _) in parts {
+            info!("Dry run: would write {}", path.display());
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Serializes a config the way the output options ask for: sorted with
+/// `--canonical` or `--stable-order`, then with the document start marker
+/// and header.
+fn render_output(
+    matches: &ArgMatches,
+    config: &mut KubeConfig,
+    files: usize,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let canonical = matches.get_flag("canonical");
+    if canonical || matches.get_flag("stable-order") {
+        config.sort_entries();
+    }
+    let mut yaml_output = if canonical {
+        config.to_canonical_yaml()?
+    } else {
+        serde_yml::to_string(config)?
+    };
+    if matches.get_flag("document-start") && !yaml_output.starts_with("---") {
+        yaml_output.insert_str(0, "---\n");
+    }
+    // The header goes first so the marker stays directly above the document.
+    if let Some(template) = matches.get_one::<String>("header") {
+        yaml_output.insert_str(0, &render_header(template, files));
+    }
+    Ok(yaml_output)
+}
+
+/// Writes one `--output-template` group. Like the output file, an unchanged
+/// group is left alone and an existing one is backed up before it is
+/// replaced.
+fn write_group_file(
+    path: &Path,
+    group: &KubeConfig,
+    content: &str,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path_name = path.to_string_lossy();
+    if path.is_file() {
+        if !force && is_unchanged(&path_name, content) {
+            info!("No changes, {} is already up to date", path_name);
+            return Ok(());
+        }
+        warn_if_exposed(&path_name)?;
+        create_backup(&path_name)?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path_name, e))?;
+    info!(
+        "Wrote {} contexts to {}",
+        group.contexts.iter().flatten().count(),
+        path_name
+    );
+    Ok(())
+}
+
+fn print_diff(
+    format: &str,
+    existing: &str,
+    yaml_output: &str,
+    merged_config: &KubeConfig,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if format == "unified" {
+        print!("{}", unified_diff(existing, yaml_output, output_file));
+        return Ok(());
+    }
+
+    let current: KubeConfig = if existing.trim().is_empty() {
+        KubeConfig::default()
+    } else {
+        serde_yml::from_str(existing)
+            .map_err(|e| format!("Failed to parse {}: {}", output_file, e))?
+    };
+    let changes = entry_diff(&current, merged_config);
+    if changes.is_empty() {
+        info!("No changes to {}", output_file);
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+/// How many of the slowest files `--parse-only` lists.
+const SLOWEST_FILES: usize = 5;
+
+/// Parses every file on its own and reports how long each took, without
+/// merging or writing anything.
+fn run_parse_only(
+    files: &[PathBuf],
+    mut archive_members: HashMap<PathBuf, Vec<u8>>,
+    options: &MergeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let mut timings = Vec::new();
+    let mut failed = 0;
+    for file in files {
+        let file_started = Instant::now();
+        let parsed = match archive_members.remove(file) {
+            Some(content) => parse_kubeconfig(file, content, options),
+            None => load_kubeconfig(file, options),
+        };
+        let elapsed = file_started.elapsed();
+        match parsed {
+            Ok(Some(_)) => info!("ok      {:>10.2?}  {}", elapsed, file.display()),
+            Ok(None) => info!("skipped {:>10.2?}  {}", elapsed, file.display()),
+            Err(e) => {
+                failed += 1;
+                error!("failed  {:>10.2?}  {}", elapsed, e);
+            }
+        }
+        timings.push((elapsed, file));
+    }
+
+    timings.sort_by_key(|(elapsed, _)| Reverse(*elapsed));
+    info!(
+        "Parsed {} files in {:.2?}, {} failed",
+        files.len(),
+        started.elapsed(),
+        failed
+    );
+    info!("Slowest files:");
+    for (elapsed, file) in timings.iter().take(SLOWEST_FILES) {
+        info!("  - {:.2?}  {}", elapsed, file.display());
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} files failed to parse", failed, files.len()).into());
+    }
+    Ok(())
+}
+
+/// Reads the current output file for options that build on it, or an empty
+/// config when it does not exist yet.
+fn load_existing_output(
+    output_file: &str,
+    option: &str,
+) -> Result<KubeConfig, Box<dyn std::error::Error>> {
+    if !Path::new(output_file).is_file() {
+        return Ok(KubeConfig::default());
+    }
+    let content = fs::read_to_string(output_file)?;
+    Ok(serde_yml::from_str(&content)
+        .map_err(|e| format!("Failed to parse {} for {}: {}", output_file, option, e))?)
+}
+
+/// Writes the context aliases as a YAML map from short name to context
+/// name, leaving the file alone when it already holds them.
+fn write_aliases(
+    path: &str,
+    aliases: &BTreeMap<String, String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = if aliases.is_empty() {
+        String::new()
+    } else {
+        serde_yml::to_string(aliases)?
+    };
+    if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        debug!("Aliases in {} are up to date", path);
+        return Ok(());
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write aliases to {}: {}", path, e))?;
+    info!("Wrote {} context aliases to {}", aliases.len(), path);
+    Ok(())
+}
 
+/// Leaves the output file out of the files found for merging. Merging the
+/// previous output back in would keep entries alive after their source
+/// files are gone.
+fn drop_own_output(
+    files: Vec<PathBuf>,
+    output_file: &str,
+    excluded: &mut Vec<(PathBuf, SkipReason)>,
+) -> Vec<PathBuf> {
+    let (own_output, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|file| same_file(file, Path::new(output_file)));
+    for file in own_output {
+        warn!(
+            "Not merging {} into itself, it is the output file",
+            file.display()
+        );
+        excluded.push((file, SkipReason::OwnOutput));
+    }
+    files
+}
+
+fn run_undo(output_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let latest = list_backups(output_file)?
+        .pop()
+        .map(|backup| backup.path)
+        .ok_or_else(|| format!("No backups of {} found", output_file))?;
+    fs::rename(&latest, output_file)
+        .map_err(|e| format!("Failed to restore {}: {}", latest.display(), e))?;
+    info!("Restored {} from {}", output_file, latest.display());
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct BackupListing {
+    path: PathBuf,
+    created: String,
+    size: u64,
+    age_seconds: i64,
+}
+
+fn run_list_backups(output_file: &str, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let now = Local::now().naive_local();
+    let mut listings = Vec::new();
+    for backup in list_backups(output_file)?.into_iter().rev() {
+        listings.push(BackupListing {
+            size: fs::metadata(&backup.path)?.len(),
+            created: backup.created.format("%Y-%m-%d %H:%M:%S").to_string(),
+            age_seconds: (now - backup.created).num_seconds(),
+            path: backup.path,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listings)?);
+        return Ok(());
+    }
+    if listings.is_empty() {
+        info!("No backups of {} found", output_file);
+        return Ok(());
+    }
+    for listing in &listings {
+        println!(
+            "{}  {:>9}  {:<14}  {}",
+            listing.created,
+            format_size(listing.size),
+            format_age(TimeDelta::seconds(listing.age_seconds)),
+            listing.path.display()
+        );
+    }
+    Ok(())
+}
+
+fn run_describe(file: &Path, context: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_kubeconfig(file, &MergeOptions::default())?
+        .ok_or_else(|| format!("{} is empty", file.display()))?;
+    match describe_context(&config, context) {
+        Some(description) => {
+            print!("{}", description);
+            Ok(())
+        }
+        None => Err(format!("Context '{}' not found in {}", context, file.display()).into()),
+    }
+}
+
+fn run_doctor(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Checking {}", file.display());
+    let config = load_kubeconfig(file, &MergeOptions::default())?
+        .ok_or_else(|| format!("{} is empty", file.display()))?;
+    let checked = merge_configs([(file.to_path_buf(), config)], &MergeOptions::default())?;
+
+    let mut issues = validate_config(
+        &checked.config,
+        &checked.sources,
+        &ValidateOptions::default(),
+    );
+    issues.extend(doctor_checks(&checked.config, &checked.sources));
+    print_categorized(&issues);
+
+    if has_errors(&issues) {
+        return Err(format!("{} has errors", file.display()).into());
+    }
     Ok(())
 }
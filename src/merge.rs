@@ -1,177 +1,1253 @@
-use crate::config::{KubeConfig, NamedCluster, NamedContext, NamedUser};
-use std::collections::HashMap;
+use crate::config::{API_VERSION, KubeConfig, NamedCluster, NamedContext, NamedUser};
+use crate::decrypt::{decrypt, detect_encryption};
+use crate::denylist::{TokenDenylist, scrub_denied_tokens};
+use crate::secret::secret_kubeconfig;
+use crate::token::token_expiry;
+use crate::utils::{expand_env_vars, is_permission_denied, read_with_timeout, same_file};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_yml::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
-pub fn merge_kubeconfigs(files: &[PathBuf]) -> Result<KubeConfig, Box<dyn std::error::Error>> {
-    let mut all_clusters = Vec::new();
-    let mut all_contexts = Vec::new();
-    let mut all_users = Vec::new();
-    let mut current_context = String::new();
-    let mut preferences = HashMap::new();
-    let mut processed_files = 0;
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NamespaceConflict {
+    /// Keep the namespace of the first definition.
+    #[default]
+    First,
+    /// Prefer a set namespace over an unset one; fail if both differ.
+    Specific,
+    /// Fail on any namespace difference.
+    Error,
+}
 
-    for file_path in files {
-        info!("Processing: {}", file_path.display());
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// JSON if the content starts with `{`, YAML otherwise.
+    #[default]
+    Auto,
+    Yaml,
+    Json,
+}
+
+#[derive(Debug, Default)]
+pub struct MergeOptions {
+    pub no_duplicates: bool,
+    pub expand_env: bool,
+    pub allow_undefined: bool,
+    pub relative_cert_paths: bool,
+    pub namespace_conflict: NamespaceConflict,
+    pub read_timeout: Option<Duration>,
+    pub input_format: InputFormat,
+    pub decrypt: bool,
+    /// File whose `current-context` wins over the first-wins default.
+    pub primary_file: Option<PathBuf>,
+    /// Record each entry's source file under [`SOURCE_ANNOTATION`].
+    pub annotate_source: bool,
+    /// Combine same-named contexts with the same cluster and user instead
+    /// of keeping only the first.
+    pub union_duplicate_contexts: bool,
+    /// Merge nested preference maps key by key and union lists instead of
+    /// replacing whole preference values.
+    pub deep_merge_preferences: bool,
+    /// Read the kubeconfig embedded in files that are Kubernetes `Secret`
+    /// manifests.
+    pub from_secrets: bool,
+    /// Drop every input's preferences instead of merging them.
+    pub ignore_preferences: bool,
+    /// Treat names that differ only in case as the same entry, keeping the
+    /// casing of whichever was merged first.
+    pub case_insensitive_names: bool,
+    /// Trim surrounding whitespace from names, servers, tokens, and paths
+    /// before comparing entries.
+    pub trim_whitespace: bool,
+    /// `apiVersion` to write instead of the one carried over from the inputs.
+    pub api_version: Option<String>,
+    /// Abort on unreadable files instead of skipping them.
+    pub strict_io: bool,
+    /// Drop users whose token is on this denylist from each input, before
+    /// duplicates are resolved.
+    pub token_denylist: Option<TokenDenylist>,
+    /// Fail on a denylisted token instead of dropping its user.
+    pub strict_denylist: bool,
+    /// Of two same-named users whose tokens carry an expiry, keep the one
+    /// expiring later instead of the first.
+    pub prefer_fresher_tokens: bool,
+}
+
+/// Key holding the name of the file an entry was merged from.
+pub const SOURCE_ANNOTATION: &str = "kubemerge.io/source";
 
-        let content = fs::read_to_string(file_path)
-            .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Cluster,
+    Context,
+    User,
+}
 
-        if content.trim().is_empty() {
-            debug!("Skipping empty file: {}", file_path.display());
-            continue;
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::Cluster => write!(f, "cluster"),
+            EntryKind::Context => write!(f, "context"),
+            EntryKind::User => write!(f, "user"),
         }
+    }
+}
 
-        let config: KubeConfig = serde_yml::from_str(&content)
-            .map_err(|e| format!("Failed to parse {}: {}", file_path.display(), e))?;
+/// Maps each merged entry to the file it was taken from.
+pub type Sources = HashMap<(EntryKind, String), PathBuf>;
 
-        let added_items = merge_config_items(
-            &config,
-            &mut all_clusters,
-            &mut all_contexts,
-            &mut all_users,
-        );
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    DuplicateIdentical,
+    DuplicateConflicting,
+    Excluded,
+    OwnOutput,
+    Pruned,
+    Coalesced,
+    RenameCollision,
+    NotSelected,
+    Pinned,
+    Expired,
+    ServerNotAllowed,
+    DeniedToken,
+    StaleToken,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            SkipReason::DuplicateIdentical => "duplicate name (identical)",
+            SkipReason::DuplicateConflicting => "duplicate name (conflicting content)",
+            SkipReason::Excluded => "excluded by pattern",
+            SkipReason::OwnOutput => "is the output file",
+            SkipReason::Pruned => "pruned",
+            SkipReason::Coalesced => "coalesced into an equivalent cluster",
+            SkipReason::RenameCollision => "renamed onto an existing name",
+            SkipReason::NotSelected => "not selected",
+            SkipReason::Pinned => "overridden by a pinned entry",
+            SkipReason::Expired => "certificate expired",
+            SkipReason::ServerNotAllowed => "server not on the allowlist",
+            SkipReason::DeniedToken => "token on the denylist",
+            SkipReason::StaleToken => "token expires earlier",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// An entry or file that did not make it into the merged config. Excluded
+/// files have no `kind` and are named after the file.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<EntryKind>,
+    pub name: String,
+    pub source_file: PathBuf,
+    pub reason: SkipReason,
+}
 
-        if current_context.is_empty() && !config.current_context.is_empty() {
-            current_context = config.current_context;
-            info!("Using current-context: {}", current_context);
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(
+                f,
+                "{} '{}' from {}: {}",
+                kind,
+                self.name,
+                self.source_file.display(),
+                self.reason
+            ),
+            None => write!(f, "file {}: {}", self.source_file.display(), self.reason),
         }
+    }
+}
 
-        for (key, value) in config.preferences {
-            preferences.insert(key, value);
+/// One step of the merge, in the order it happened. `--explain` prints
+/// these.
+#[derive(Debug, Clone)]
+pub enum Decision {
+    Added {
+        kind: EntryKind,
+        name: String,
+        source_file: PathBuf,
+    },
+    Skipped(SkippedEntry),
+    Combined {
+        name: String,
+        source_file: PathBuf,
+    },
+    CurrentContext {
+        name: String,
+        source_file: PathBuf,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Added {
+                kind,
+                name,
+                source_file,
+            } => write!(
+                f,
+                "Added {} '{}' from {}",
+                kind,
+                name,
+                source_file.display()
+            ),
+            Decision::Skipped(entry) => match (entry.reason, entry.kind) {
+                (SkipReason::Pruned, Some(kind)) => write!(
+                    f,
+                    "Pruned {} '{}' from {}",
+                    kind,
+                    entry.name,
+                    entry.source_file.display()
+                ),
+                _ => write!(f, "Skipped {}", entry),
+            },
+            Decision::Combined { name, source_file } => write!(
+                f,
+                "Combined context '{}' from {} into its earlier definition",
+                name,
+                source_file.display()
+            ),
+            Decision::CurrentContext {
+                name,
+                source_file,
+                reason,
+            } => write!(
+                f,
+                "current-context set to '{}' from {} ({})",
+                name,
+                source_file.display(),
+                reason
+            ),
         }
+    }
+}
 
-        if added_items > 0 {
-            processed_files += 1;
-            info!("Added {} items from {}", added_items, file_path.display());
-        } else {
-            debug!("No new items added from {}", file_path.display());
+#[derive(Debug)]
+pub struct MergeResult {
+    pub config: KubeConfig,
+    pub sources: Sources,
+    pub skipped: Vec<SkippedEntry>,
+    pub decisions: Vec<Decision>,
+    /// Name each renamed entry had when it was merged, keyed by its
+    /// current name.
+    pub original_names: HashMap<(EntryKind, String), String>,
+}
+
+impl MergeResult {
+    /// Records an entry or file left out of the merge.
+    pub fn skip(&mut self, entry: SkippedEntry) {
+        self.decisions.push(Decision::Skipped(entry.clone()));
+        self.skipped.push(entry);
+    }
+}
+
+/// Reads and merges kubeconfig files in order; earlier files take precedence.
+pub fn merge_kubeconfigs(
+    files: &[PathBuf],
+    options: &MergeOptions,
+) -> Result<MergeResult, Box<dyn std::error::Error>> {
+    let mut merger = Merger::new(options);
+    for file_path in files {
+        info!("Processing: {}", file_path.display());
+        if let Some(config) = load_kubeconfig(file_path, options)? {
+            merger.add(file_path, config)?;
         }
     }
+    merger.finish()
+}
 
-    if processed_files == 0 {
-        error!("No valid kubeconfig files were processed");
-        return Err("No valid kubeconfig files were processed".into());
+/// Merges kubeconfigs that were read into memory, such as archive members,
+/// each paired with a path naming where it came from.
+pub fn merge_contents<I>(
+    contents: I,
+    options: &MergeOptions,
+) -> Result<MergeResult, Box<dyn std::error::Error>>
+where
+    I: IntoIterator<Item = (PathBuf, Vec<u8>)>,
+{
+    let mut merger = Merger::new(options);
+    for (source, content) in contents {
+        info!("Processing: {}", source.display());
+        if let Some(config) = parse_kubeconfig(&source, content, options)? {
+            merger.add(&source, config)?;
+        }
     }
+    merger.finish()
+}
 
-    let merged = KubeConfig {
-        api_version: "v1".to_string(),
-        kind: "Config".to_string(),
-        clusters: if all_clusters.is_empty() {
-            None
-        } else {
-            Some(all_clusters)
-        },
-        contexts: if all_contexts.is_empty() {
-            None
-        } else {
-            Some(all_contexts)
-        },
-        users: if all_users.is_empty() {
-            None
-        } else {
-            Some(all_users)
+/// Merges already-parsed configs, each paired with the source it came from.
+/// Configs are consumed one at a time, so callers can produce them lazily.
+pub fn merge_configs<I>(
+    configs: I,
+    options: &MergeOptions,
+) -> Result<MergeResult, Box<dyn std::error::Error>>
+where
+    I: IntoIterator<Item = (PathBuf, KubeConfig)>,
+{
+    let mut merger = Merger::new(options);
+    for (source, config) in configs {
+        merger.add(&source, config)?;
+    }
+    merger.finish()
+}
+
+/// Reads and parses a single kubeconfig file. Returns `Ok(None)` for files
+/// that are empty, could not be read in time, or are not readable by us
+/// (unless `strict_io` is set).
+pub fn load_kubeconfig(
+    file_path: &Path,
+    options: &MergeOptions,
+) -> Result<Option<KubeConfig>, Box<dyn std::error::Error>> {
+    let read = match options.read_timeout {
+        Some(timeout) => read_with_timeout(file_path, timeout),
+        None => fs::read(file_path).map(Some),
+    };
+    let content = match read {
+        Ok(Some(content)) => content,
+        Ok(None) => {
+            warn!(
+                "Timed out reading {} after {:?}, skipping",
+                file_path.display(),
+                options.read_timeout.unwrap_or_default()
+            );
+            return Ok(None);
+        }
+        Err(e) if is_permission_denied(&e, options.strict_io) => {
+            warn!("Permission denied: {}, skipping", file_path.display());
+            return Ok(None);
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", file_path.display(), e).into()),
+    };
+    parse_kubeconfig(file_path, content, options)
+}
+
+/// Parses the raw content of a kubeconfig read from `file_path`, decrypting
+/// or unwrapping it as the options allow. Returns `Ok(None)` for content
+/// that is empty or had to be skipped.
+pub fn parse_kubeconfig(
+    file_path: &Path,
+    content: Vec<u8>,
+    options: &MergeOptions,
+) -> Result<Option<KubeConfig>, Box<dyn std::error::Error>> {
+    let content = match detect_encryption(&content) {
+        Some(encryption) if options.decrypt => match decrypt(file_path, &content, encryption) {
+            Ok(plaintext) => {
+                debug!("Decrypted {} ({:?})", file_path.display(), encryption);
+                plaintext
+            }
+            Err(e) => {
+                warn!("Failed to decrypt {}: {}, skipping", file_path.display(), e);
+                return Ok(None);
+            }
         },
-        current_context,
-        preferences,
+        Some(_) => {
+            warn!(
+                "Skipping {}, it is encrypted (use --decrypt to read it)",
+                file_path.display()
+            );
+            return Ok(None);
+        }
+        None => String::from_utf8(content)
+            .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?,
     };
+    let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
 
-    validate_config(&merged)?;
-    Ok(merged)
+    if content.trim().is_empty() {
+        debug!("Skipping empty file: {}", file_path.display());
+        return Ok(None);
+    }
+
+    let embedded;
+    let content = if options.from_secrets {
+        match secret_kubeconfig(content) {
+            Ok(Some(kubeconfig)) => {
+                debug!("Read kubeconfig from Secret {}", file_path.display());
+                embedded = kubeconfig;
+                embedded.as_str()
+            }
+            Ok(None) => content,
+            Err(e) => {
+                warn!("Skipping {}: {}", file_path.display(), e);
+                return Ok(None);
+            }
+        }
+    } else {
+        content
+    };
+
+    let json = match options.input_format {
+        InputFormat::Yaml => false,
+        InputFormat::Json => true,
+        InputFormat::Auto => content.trim_start().starts_with('{'),
+    };
+    let parsed: Result<KubeConfig, String> = if json {
+        serde_json::from_str(content).map_err(|e| e.to_string())
+    } else {
+        serde_yml::from_str(content).map_err(|e| e.to_string())
+    };
+    let config = parsed.map_err(|e| format!("Failed to parse {}: {}", file_path.display(), e))?;
+    Ok(Some(config))
 }
 
-fn merge_config_items(
-    config: &KubeConfig,
-    all_clusters: &mut Vec<NamedCluster>,
-    all_contexts: &mut Vec<NamedContext>,
-    all_users: &mut Vec<NamedUser>,
-) -> usize {
-    let mut added_items = 0;
+/// Accumulates configs into a single merged config. Only the merged entries
+/// are retained between calls to [`Merger::add`].
+#[derive(Debug)]
+pub struct Merger<'a> {
+    options: &'a MergeOptions,
+    clusters: Vec<NamedCluster>,
+    contexts: Vec<NamedContext>,
+    users: Vec<NamedUser>,
+    /// Positions of merged entries by name key (see [`name_key`]), so
+    /// duplicates are found without scanning the lists.
+    cluster_index: HashMap<String, usize>,
+    context_index: HashMap<String, usize>,
+    user_index: HashMap<String, usize>,
+    sources: Sources,
+    skipped: Vec<SkippedEntry>,
+    decisions: Vec<Decision>,
+    current_context: String,
+    primary_seen: bool,
+    /// Distinct `apiVersion`s of the inputs, in the order first seen.
+    api_versions: Vec<String>,
+    primary_api_version: Option<String>,
+    preferences: BTreeMap<String, Value>,
+    other: BTreeMap<String, Value>,
+    processed_files: usize,
+}
 
-    if let Some(clusters) = &config.clusters {
-        for cluster in clusters {
-            if !all_clusters.iter().any(|c| c.name == cluster.name) {
-                debug!("Adding cluster: {}", cluster.name);
-                all_clusters.push(cluster.clone());
-                added_items += 1;
-            } else {
-                debug!("Skipping duplicate cluster: {}", cluster.name);
+impl<'a> Merger<'a> {
+    pub fn new(options: &'a MergeOptions) -> Self {
+        Merger {
+            options,
+            clusters: Vec::new(),
+            contexts: Vec::new(),
+            users: Vec::new(),
+            cluster_index: HashMap::new(),
+            context_index: HashMap::new(),
+            user_index: HashMap::new(),
+            sources: Sources::new(),
+            skipped: Vec::new(),
+            decisions: Vec::new(),
+            current_context: String::new(),
+            primary_seen: false,
+            api_versions: Vec::new(),
+            primary_api_version: None,
+            preferences: BTreeMap::new(),
+            other: BTreeMap::new(),
+            processed_files: 0,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        source: &Path,
+        mut config: KubeConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let options = self.options;
+        config.drop_nulls();
+        config.normalize_extensions();
+        self.record_api_version(source, &config.api_version);
+
+        if options.expand_env {
+            expand_config_env(&mut config, options.allow_undefined)
+                .map_err(|e| format!("Failed to expand {}: {}", source.display(), e))?;
+        }
+
+        if options.trim_whitespace {
+            for field in config.trim_whitespace() {
+                warn!("Trimmed whitespace from {} in {}", field, source.display());
+            }
+        }
+
+        if let Some(denylist) = &options.token_denylist {
+            for entry in
+                scrub_denied_tokens(&mut config, source, denylist, options.strict_denylist)?
+            {
+                self.decisions.push(Decision::Skipped(entry.clone()));
+                self.skipped.push(entry);
+            }
+        }
+
+        if options.relative_cert_paths {
+            resolve_cert_paths(&mut config, source)?;
+        }
+
+        let added_items = self.merge_items(&config, source)?;
+
+        let is_primary = options
+            .primary_file
+            .as_deref()
+            .is_some_and(|primary| same_file(primary, source));
+        let reason = if is_primary {
+            self.primary_seen = true;
+            self.primary_api_version = Some(config.api_version.clone());
+            Some("primary file")
+        } else if self.current_context.is_empty() {
+            Some("first non-empty")
+        } else {
+            None
+        };
+        if let Some(reason) = reason
+            && !config.current_context.is_empty()
+        {
+            self.current_context = config.current_context;
+            info!(
+                "Using current-context: {} ({})",
+                self.current_context, reason
+            );
+            self.decisions.push(Decision::CurrentContext {
+                name: self.current_context.clone(),
+                source_file: source.to_path_buf(),
+                reason,
+            });
+        }
+
+        if options.ignore_preferences && !config.preferences.is_empty() {
+            debug!("Ignoring preferences from {}", source.display());
+            config.preferences.clear();
+        }
+        for (key, value) in config.preferences {
+            if options.deep_merge_preferences
+                && let Some(existing) = self.preferences.get_mut(&key)
+            {
+                deep_merge(existing, value, &key, source);
+                continue;
+            }
+            if let Some(previous) = self.preferences.insert(key.clone(), value)
+                && previous != self.preferences[&key]
+            {
+                warn!(
+                    "Preference '{}' from {} overrides an earlier value",
+                    key,
+                    source.display()
+                );
             }
         }
+
+        for (key, value) in config.other {
+            if let Some(previous) = self.other.insert(key.clone(), value)
+                && previous != self.other[&key]
+            {
+                warn!(
+                    "Top-level key '{}' from {} overrides an earlier value",
+                    key,
+                    source.display()
+                );
+            }
+        }
+
+        if added_items > 0 {
+            self.processed_files += 1;
+            info!("Added {} items from {}", added_items, source.display());
+        } else {
+            debug!("No new items added from {}", source.display());
+        }
+
+        Ok(())
     }
 
-    if let Some(contexts) = &config.contexts {
-        for context in contexts {
-            if !all_contexts.iter().any(|c| c.name == context.name) {
-                debug!("Adding context: {}", context.name);
-                all_contexts.push(context.clone());
-                added_items += 1;
+    pub fn finish(mut self) -> Result<MergeResult, Box<dyn std::error::Error>> {
+        if self.processed_files == 0 {
+            error!("No valid kubeconfig files were processed");
+            return Err("No valid kubeconfig files were processed".into());
+        }
+        if let Some(primary) = &self.options.primary_file
+            && !self.primary_seen
+        {
+            warn!(
+                "Primary file {} is not among the merged files",
+                primary.display()
+            );
+        }
+        if self.options.case_insensitive_names {
+            self.use_kept_casing();
+        }
+        if self.options.annotate_source {
+            self.annotate_sources();
+        }
+
+        let api_version = self.output_api_version();
+        let merged = KubeConfig {
+            api_version,
+            kind: "Config".to_string(),
+            clusters: if self.clusters.is_empty() {
+                None
+            } else {
+                Some(self.clusters)
+            },
+            contexts: if self.contexts.is_empty() {
+                None
             } else {
-                debug!("Skipping duplicate context: {}", context.name);
+                Some(self.contexts)
+            },
+            users: if self.users.is_empty() {
+                None
+            } else {
+                Some(self.users)
+            },
+            current_context: self.current_context,
+            preferences: self.preferences,
+            other: self.other,
+        };
+
+        Ok(MergeResult {
+            config: merged,
+            sources: self.sources,
+            skipped: self.skipped,
+            decisions: self.decisions,
+            original_names: HashMap::new(),
+        })
+    }
+
+    fn merge_items(
+        &mut self,
+        config: &KubeConfig,
+        source: &Path,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        if self.clusters.is_empty()
+            && self.contexts.is_empty()
+            && self.users.is_empty()
+            && has_unique_names(config, self.options.case_insensitive_names)
+        {
+            return Ok(self.take_items(config, source));
+        }
+
+        let options = self.options;
+        let mut added_items = 0;
+        self.index_taken_items();
+
+        if let Some(clusters) = &config.clusters {
+            for cluster in clusters {
+                let key = name_key(&cluster.name, options);
+                if let Some(&index) = self.cluster_index.get(&key) {
+                    let existing = &self.clusters[index];
+                    log_case_collision(EntryKind::Cluster, &existing.name, &cluster.name, source);
+                    check_duplicate(
+                        EntryKind::Cluster,
+                        &existing.name,
+                        source,
+                        options,
+                        &self.sources,
+                    )?;
+                    let identical = existing.cluster == cluster.cluster;
+                    self.skip_duplicate(EntryKind::Cluster, &cluster.name, source, identical);
+                } else {
+                    debug!("Adding cluster: {}", cluster.name);
+                    self.record_added(EntryKind::Cluster, &cluster.name, source);
+                    self.cluster_index.insert(key, self.clusters.len());
+                    self.clusters.push(cluster.clone());
+                    self.sources.insert(
+                        (EntryKind::Cluster, cluster.name.clone()),
+                        source.to_path_buf(),
+                    );
+                    added_items += 1;
+                }
+            }
+        }
+
+        if let Some(contexts) = &config.contexts {
+            for context in contexts {
+                let key = name_key(&context.name, options);
+                if let Some(&index) = self.context_index.get(&key) {
+                    let existing = &mut self.contexts[index];
+                    log_case_collision(EntryKind::Context, &existing.name, &context.name, source);
+                    check_duplicate(
+                        EntryKind::Context,
+                        &existing.name,
+                        source,
+                        options,
+                        &self.sources,
+                    )?;
+                    if options.union_duplicate_contexts
+                        && existing.context.cluster == context.context.cluster
+                        && existing.context.user == context.context.user
+                        && existing.context != context.context
+                    {
+                        union_context(existing, context, source);
+                        self.decisions.push(Decision::Combined {
+                            name: context.name.clone(),
+                            source_file: source.to_path_buf(),
+                        });
+                        continue;
+                    }
+                    resolve_namespace(existing, context, source, options.namespace_conflict)?;
+                    let identical = existing.context == context.context;
+                    self.skip_duplicate(EntryKind::Context, &context.name, source, identical);
+                } else {
+                    debug!("Adding context: {}", context.name);
+                    self.record_added(EntryKind::Context, &context.name, source);
+                    self.context_index.insert(key, self.contexts.len());
+                    self.contexts.push(context.clone());
+                    self.sources.insert(
+                        (EntryKind::Context, context.name.clone()),
+                        source.to_path_buf(),
+                    );
+                    added_items += 1;
+                }
+            }
+        }
+
+        if let Some(users) = &config.users {
+            for user in users {
+                let key = name_key(&user.name, options);
+                if let Some(&index) = self.user_index.get(&key) {
+                    let existing = &self.users[index];
+                    log_case_collision(EntryKind::User, &existing.name, &user.name, source);
+                    check_duplicate(
+                        EntryKind::User,
+                        &existing.name,
+                        source,
+                        options,
+                        &self.sources,
+                    )?;
+                    let identical = existing.user == user.user;
+                    if !identical
+                        && options.prefer_fresher_tokens
+                        && let (Some(current), Some(incoming)) =
+                            (token_expiry(&existing.user), token_expiry(&user.user))
+                        && incoming > current
+                    {
+                        self.replace_stale_user(index, user, source, current, incoming);
+                        continue;
+                    }
+                    self.skip_duplicate(EntryKind::User, &user.name, source, identical);
+                } else {
+                    debug!("Adding user: {}", user.name);
+                    self.record_added(EntryKind::User, &user.name, source);
+                    self.user_index.insert(key, self.users.len());
+                    self.users.push(user.clone());
+                    self.sources
+                        .insert((EntryKind::User, user.name.clone()), source.to_path_buf());
+                    added_items += 1;
+                }
             }
         }
+
+        Ok(added_items)
     }
 
-    if let Some(users) = &config.users {
-        for user in users {
-            if !all_users.iter().any(|u| u.name == user.name) {
-                debug!("Adding user: {}", user.name);
-                all_users.push(user.clone());
-                added_items += 1;
-            } else {
-                debug!("Skipping duplicate user: {}", user.name);
+    /// Fast path for the first config, typically the only one: with nothing
+    /// merged yet and no repeated names there are no duplicates to resolve,
+    /// so entries are taken as they are without per-entry lookups.
+    fn take_items(&mut self, config: &KubeConfig, source: &Path) -> usize {
+        let clusters = config.clusters.iter().flatten();
+        let contexts = config.contexts.iter().flatten();
+        let users = config.users.iter().flatten();
+
+        let keys = clusters
+            .clone()
+            .map(|c| (EntryKind::Cluster, c.name.clone()))
+            .chain(
+                contexts
+                    .clone()
+                    .map(|c| (EntryKind::Context, c.name.clone())),
+            )
+            .chain(users.clone().map(|u| (EntryKind::User, u.name.clone())));
+        for (kind, name) in keys {
+            self.record_added(kind, &name, source);
+            self.sources.insert((kind, name), source.to_path_buf());
+        }
+        self.clusters.extend(clusters.cloned());
+        self.contexts.extend(contexts.cloned());
+        self.users.extend(users.cloned());
+
+        debug!("Took all entries from {} as-is", source.display());
+        self.clusters.len() + self.contexts.len() + self.users.len()
+    }
+
+    /// Indexes the entries [`Merger::take_items`] took, which skips indexing
+    /// so that merging a single file does not pay for it.
+    fn index_taken_items(&mut self) {
+        let options = self.options;
+        if self.cluster_index.is_empty() {
+            for (index, cluster) in self.clusters.iter().enumerate() {
+                self.cluster_index
+                    .insert(name_key(&cluster.name, options), index);
+            }
+        }
+        if self.context_index.is_empty() {
+            for (index, context) in self.contexts.iter().enumerate() {
+                self.context_index
+                    .insert(name_key(&context.name, options), index);
+            }
+        }
+        if self.user_index.is_empty() {
+            for (index, user) in self.users.iter().enumerate() {
+                self.user_index.insert(name_key(&user.name, options), index);
             }
         }
     }
 
-    added_items
-}
+    /// Points context references and the current context at the casing that
+    /// was kept, so entries folded by `case_insensitive_names` do not leave
+    /// references to the casing that was dropped.
+    fn use_kept_casing(&mut self) {
+        self.index_taken_items();
+        for context in &mut self.contexts {
+            let kept_cluster = self
+                .cluster_index
+                .get(&context.context.cluster.to_lowercase())
+                .map(|&index| &self.clusters[index].name);
+            let kept_user = self
+                .user_index
+                .get(&context.context.user.to_lowercase())
+                .map(|&index| &self.users[index].name);
+            let references = [
+                (
+                    EntryKind::Cluster,
+                    &mut context.context.cluster,
+                    kept_cluster,
+                ),
+                (EntryKind::User, &mut context.context.user, kept_user),
+            ];
+            for (kind, reference, kept) in references {
+                if let Some(kept) = kept
+                    && kept != reference
+                {
+                    info!(
+                        "Pointing context '{}' at {} '{}' instead of '{}'",
+                        context.name, kind, kept, reference
+                    );
+                    *reference = kept.clone();
+                }
+            }
+        }
+        if let Some(&index) = self.context_index.get(&self.current_context.to_lowercase()) {
+            self.current_context = self.contexts[index].name.clone();
+        }
+    }
 
-fn validate_config(config: &KubeConfig) -> Result<(), Box<dyn std::error::Error>> {
-    if !config.current_context.is_empty() {
-        if let Some(contexts) = &config.contexts {
-            if !contexts.iter().any(|c| c.name == config.current_context) {
-                error!(
-                    "Current context '{}' not found in merged contexts",
-                    config.current_context
+    /// Annotates entries only once merging is done, so the annotation does
+    /// not affect duplicate detection.
+    fn annotate_sources(&mut self) {
+        let sources = &self.sources;
+        let annotate = |kind: EntryKind, name: &str, other: &mut BTreeMap<String, Value>| {
+            if let Some(file_name) = sources
+                .get(&(kind, name.to_string()))
+                .and_then(|source| source.file_name())
+            {
+                other.insert(
+                    SOURCE_ANNOTATION.to_string(),
+                    Value::String(file_name.to_string_lossy().into_owned()),
                 );
-                return Err(format!(
-                    "Current context '{}' not found in merged contexts",
-                    config.current_context
-                )
-                .into());
             }
+        };
+
+        for cluster in &mut self.clusters {
+            annotate(
+                EntryKind::Cluster,
+                &cluster.name,
+                &mut cluster.cluster.other,
+            );
+        }
+        for context in &mut self.contexts {
+            annotate(
+                EntryKind::Context,
+                &context.name,
+                &mut context.context.other,
+            );
+        }
+        for user in &mut self.users {
+            annotate(EntryKind::User, &user.name, &mut user.user.other);
         }
     }
 
-    if let Some(contexts) = &config.contexts {
-        let cluster_names: Vec<&String> = config
-            .clusters
-            .as_ref()
-            .map(|c| c.iter().map(|cluster| &cluster.name).collect())
-            .unwrap_or_default();
-        let user_names: Vec<&String> = config
-            .users
-            .as_ref()
-            .map(|u| u.iter().map(|user| &user.name).collect())
-            .unwrap_or_default();
+    fn record_added(&mut self, kind: EntryKind, name: &str, source: &Path) {
+        self.decisions.push(Decision::Added {
+            kind,
+            name: name.to_string(),
+            source_file: source.to_path_buf(),
+        });
+    }
+
+    fn record_api_version(&mut self, source: &Path, api_version: &str) {
+        let earlier = self
+            .api_versions
+            .first()
+            .filter(|first| *first != api_version);
+        match (api_version != API_VERSION, earlier) {
+            (true, Some(first)) => warn!(
+                "{} has apiVersion '{}', not {} and unlike '{}' of earlier inputs",
+                source.display(),
+                api_version,
+                API_VERSION,
+                first
+            ),
+            (true, None) => warn!(
+                "{} has apiVersion '{}', not {}",
+                source.display(),
+                api_version,
+                API_VERSION
+            ),
+            (false, Some(first)) => warn!(
+                "{} has apiVersion '{}', unlike '{}' of earlier inputs",
+                source.display(),
+                api_version,
+                first
+            ),
+            (false, None) => {}
+        }
+        if !self.api_versions.iter().any(|seen| seen == api_version) {
+            self.api_versions.push(api_version.to_string());
+        }
+    }
 
-        for context in contexts {
-            if !cluster_names.contains(&&context.context.cluster) {
+    /// The configured `apiVersion`, else the primary file's, else the one
+    /// all inputs share. Inputs that disagree fall back to `v1`.
+    fn output_api_version(&mut self) -> String {
+        if let Some(api_version) = &self.options.api_version {
+            return api_version.clone();
+        }
+        if let Some(api_version) = self.primary_api_version.take() {
+            return api_version;
+        }
+        match self.api_versions.as_slice() {
+            [api_version] => api_version.clone(),
+            [] => API_VERSION.to_string(),
+            versions => {
                 warn!(
-                    "Context '{}' references missing cluster '{}'",
-                    context.name, context.context.cluster
+                    "Inputs disagree on apiVersion ({}), writing {}; use --api-version to choose",
+                    versions.join(", "),
+                    API_VERSION
                 );
+                API_VERSION.to_string()
+            }
+        }
+    }
+
+    /// Swaps the user at `index` for a same-named one from `source` whose
+    /// token expires later, recording the earlier one as skipped.
+    fn replace_stale_user(
+        &mut self,
+        index: usize,
+        user: &NamedUser,
+        source: &Path,
+        current: DateTime<Utc>,
+        incoming: DateTime<Utc>,
+    ) {
+        let existing = &mut self.users[index];
+        existing.user = user.user.clone();
+        let key = (EntryKind::User, existing.name.clone());
+        let previous = self
+            .sources
+            .insert(key, source.to_path_buf())
+            .unwrap_or_default();
+        info!(
+            "Taking user '{}' from {}, its token expires {} instead of {}",
+            existing.name,
+            source.display(),
+            incoming.to_rfc3339(),
+            current.to_rfc3339()
+        );
+        let entry = SkippedEntry {
+            kind: Some(EntryKind::User),
+            name: existing.name.clone(),
+            source_file: previous,
+            reason: SkipReason::StaleToken,
+        };
+        self.decisions.push(Decision::Skipped(entry.clone()));
+        self.skipped.push(entry);
+    }
+
+    fn skip_duplicate(&mut self, kind: EntryKind, name: &str, source: &Path, identical: bool) {
+        log_duplicate(kind, name, source, identical);
+        let entry = SkippedEntry {
+            kind: Some(kind),
+            name: name.to_string(),
+            source_file: source.to_path_buf(),
+            reason: if identical {
+                SkipReason::DuplicateIdentical
+            } else {
+                SkipReason::DuplicateConflicting
+            },
+        };
+        self.decisions.push(Decision::Skipped(entry.clone()));
+        self.skipped.push(entry);
+    }
+}
+
+fn has_unique_names(config: &KubeConfig, case_insensitive: bool) -> bool {
+    fn unique<'a>(mut names: impl Iterator<Item = &'a String>, case_insensitive: bool) -> bool {
+        let mut seen = HashSet::new();
+        names.all(|name| {
+            if case_insensitive {
+                seen.insert(name.to_lowercase())
+            } else {
+                seen.insert(name.clone())
             }
-            if !user_names.contains(&&context.context.user) {
+        })
+    }
+
+    unique(
+        config.clusters.iter().flatten().map(|c| &c.name),
+        case_insensitive,
+    ) && unique(
+        config.contexts.iter().flatten().map(|c| &c.name),
+        case_insensitive,
+    ) && unique(
+        config.users.iter().flatten().map(|u| &u.name),
+        case_insensitive,
+    )
+}
+
+/// The key an entry name is deduplicated under: the name itself, or its
+/// lowercase form with `case_insensitive_names`.
+fn name_key(name: &str, options: &MergeOptions) -> String {
+    if options.case_insensitive_names {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+fn log_case_collision(kind: EntryKind, existing: &str, incoming: &str, source: &Path) {
+    if existing != incoming {
+        warn!(
+            "Treating {} '{}' from {} as a duplicate of '{}', the names differ only in case",
+            kind,
+            incoming,
+            source.display(),
+            existing
+        );
+    }
+}
+
+/// Merges `incoming` into `existing`: maps key by key, lists as a union in
+/// first-seen order, and anything else last-wins like flat preferences.
+fn deep_merge(existing: &mut Value, incoming: Value, path: &str, source: &Path) {
+    match (existing, incoming) {
+        (Value::Mapping(existing), Value::Mapping(incoming)) => {
+            for (key, value) in incoming {
+                let child = match key.as_str() {
+                    Some(name) => format!("{}.{}", path, name),
+                    None => path.to_string(),
+                };
+                match existing.get_mut(&key) {
+                    Some(current) => deep_merge(current, value, &child, source),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(existing), Value::Sequence(incoming)) => {
+            for item in incoming {
+                if !existing.contains(&item) {
+                    existing.push(item);
+                }
+            }
+        }
+        (existing, incoming) => {
+            if *existing != incoming {
                 warn!(
-                    "Context '{}' references missing user '{}'",
-                    context.name, context.context.user
+                    "Preference '{}' from {} overrides an earlier value",
+                    path,
+                    source.display()
                 );
+                *existing = incoming;
             }
         }
     }
+}
+
+fn expand_config_env(config: &mut KubeConfig, allow_undefined: bool) -> Result<(), String> {
+    let expand = |field: &mut Option<String>| -> Result<(), String> {
+        if let Some(value) = field {
+            *value = expand_env_vars(value, allow_undefined)?;
+        }
+        Ok(())
+    };
+
+    for cluster in config.clusters.iter_mut().flatten() {
+        cluster.cluster.server = expand_env_vars(&cluster.cluster.server, allow_undefined)?;
+        expand(&mut cluster.cluster.certificate_authority)?;
+    }
+
+    for user in config.users.iter_mut().flatten() {
+        expand(&mut user.user.client_certificate)?;
+        expand(&mut user.user.client_key)?;
+    }
+
+    Ok(())
+}
+
+/// Rewrites relative certificate and key paths so they stay valid when the
+/// merged config is written somewhere other than the source file's directory.
+fn resolve_cert_paths(
+    config: &mut KubeConfig,
+    source: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let base_dir = std::path::absolute(source)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let resolve = |field: &mut Option<String>| {
+        if let Some(value) = field
+            && Path::new(value).is_relative()
+        {
+            let resolved = base_dir.join(&value).display().to_string();
+            debug!("Resolved {} to {}", value, resolved);
+            *value = resolved;
+        }
+    };
+
+    for cluster in config.clusters.iter_mut().flatten() {
+        resolve(&mut cluster.cluster.certificate_authority);
+    }
+
+    for user in config.users.iter_mut().flatten() {
+        resolve(&mut user.user.client_certificate);
+        resolve(&mut user.user.client_key);
+    }
 
     Ok(())
 }
+
+fn resolve_namespace(
+    existing: &mut NamedContext,
+    incoming: &NamedContext,
+    source: &Path,
+    strategy: NamespaceConflict,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let current = &existing.context.namespace;
+    let candidate = &incoming.context.namespace;
+    if current == candidate {
+        return Ok(());
+    }
+
+    match (strategy, current, candidate) {
+        (NamespaceConflict::First, _, _) => Ok(()),
+        (NamespaceConflict::Specific, None, Some(namespace)) => {
+            info!(
+                "Using namespace '{}' for context '{}' from {}",
+                namespace,
+                existing.name,
+                source.display()
+            );
+            existing.context.namespace = Some(namespace.clone());
+            Ok(())
+        }
+        (NamespaceConflict::Specific, Some(_), None) => Ok(()),
+        _ => Err(format!(
+            "Context '{}' has conflicting namespaces '{}' and '{}' (from {})",
+            existing.name,
+            current.as_deref().unwrap_or("<none>"),
+            candidate.as_deref().unwrap_or("<none>"),
+            source.display()
+        )
+        .into()),
+    }
+}
+
+/// Fills in whatever `existing` lacks from `incoming`: an unset namespace and
+/// missing extra keys. Conflicting values keep the earlier definition.
+fn union_context(existing: &mut NamedContext, incoming: &NamedContext, source: &Path) {
+    let name = &existing.name;
+    match (&existing.context.namespace, &incoming.context.namespace) {
+        (None, Some(namespace)) => existing.context.namespace = Some(namespace.clone()),
+        (Some(current), Some(namespace)) if current != namespace => warn!(
+            "Context '{}' from {} has namespace '{}', keeping '{}'",
+            name,
+            source.display(),
+            namespace,
+            current
+        ),
+        _ => {}
+    }
+
+    for extension in incoming.context.extensions.iter().flatten() {
+        let extensions = existing.context.extensions.get_or_insert_with(Vec::new);
+        match extensions.iter().find(|e| e.name == extension.name) {
+            None => extensions.push(extension.clone()),
+            Some(current) if current != extension => warn!(
+                "Context '{}' from {} has a different extension '{}', keeping the earlier value",
+                name,
+                source.display(),
+                extension.name
+            ),
+            Some(_) => {}
+        }
+    }
+    if let Some(extensions) = &mut existing.context.extensions {
+        extensions.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    for (key, value) in &incoming.context.other {
+        match existing.context.other.get(key) {
+            None => {
+                existing.context.other.insert(key.clone(), value.clone());
+            }
+            Some(current) if current != value => warn!(
+                "Context '{}' from {} has a different '{}', keeping the earlier value",
+                name,
+                source.display(),
+                key
+            ),
+            Some(_) => {}
+        }
+    }
+    info!(
+        "Combined context '{}' with its definition from {}",
+        name,
+        source.display()
+    );
+}
+
+fn log_duplicate(kind: EntryKind, name: &str, source: &Path, identical: bool) {
+    if identical {
+        debug!("Skipping identical duplicate {}: {}", kind, name);
+    } else {
+        warn!(
+            "Skipping conflicting {} '{}' from {}, keeping the earlier definition",
+            kind,
+            name,
+            source.display()
+        );
+    }
+}
+
+fn check_duplicate(
+    kind: EntryKind,
+    name: &str,
+    source: &Path,
+    options: &MergeOptions,
+    sources: &Sources,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !options.no_duplicates {
+        return Ok(());
+    }
+
+    match sources.get(&(kind, name.to_string())) {
+        Some(first) if first != source => {
+            error!(
+                "Duplicate {} '{}' found in {} and {}",
+                kind,
+                name,
+                first.display(),
+                source.display()
+            );
+            Err(format!(
+                "Duplicate {} '{}' found in {} and {}",
+                kind,
+                name,
+                first.display(),
+                source.display()
+            )
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
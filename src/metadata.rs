@@ -0,0 +1,49 @@
+use crate::config::KubeConfig;
+use serde_yml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use tracing::{debug, warn};
+
+/// Prefix of the keys metadata is stored under in a context's fields.
+pub const METADATA_PREFIX: &str = "kubemerge.io/";
+
+/// Context metadata keyed by context name, then by annotation name.
+pub type ContextMetadata = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// Loads a metadata file mapping context names to annotations, e.g.
+/// `prod: {description: Production, team: platform}`.
+pub fn load_metadata(path: &str) -> Result<ContextMetadata, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read metadata file {}: {}", path, e))?;
+    let metadata: Option<ContextMetadata> = serde_yml::from_str(&content)
+        .map_err(|e| format!("Failed to parse metadata file {}: {}", path, e))?;
+    Ok(metadata.unwrap_or_default())
+}
+
+/// Adds each context's metadata to its fields as `kubemerge.io/<key>`,
+/// replacing earlier values. Warns about metadata for unknown contexts.
+pub fn apply_metadata(config: &mut KubeConfig, metadata: &ContextMetadata) {
+    for (name, annotations) in metadata {
+        let Some(context) = config
+            .contexts
+            .iter_mut()
+            .flatten()
+            .find(|context| &context.name == name)
+        else {
+            warn!("Metadata for context '{}' matches no merged context", name);
+            continue;
+        };
+
+        for (key, value) in annotations {
+            context
+                .context
+                .other
+                .insert(format!("{}{}", METADATA_PREFIX, key), value.clone());
+        }
+        debug!(
+            "Added {} metadata entries to context '{}'",
+            annotations.len(),
+            name
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::{KubeConfig, NamedCluster, NamedContext, NamedUser};
+use crate::merge::{EntryKind, MergeResult, SkipReason, SkippedEntry};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Reads a pins file: one entry name per line, with blank lines and `#`
+/// comments ignored.
+pub fn load_pins(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read pins file {}: {}", path.display(), e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Restores pinned entries verbatim from the existing output. A pin names
+/// clusters, contexts, and users alike; the existing entry replaces any
+/// merged entry of the same kind and name, which is recorded as skipped.
+/// Pinned entries are dropped from the result's sources, so `--sync` treats
+/// them as unmanaged and never removes them.
+pub fn apply_pins(result: &mut MergeResult, existing: &KubeConfig, pins: &[String]) {
+    let pins: HashSet<&str> = pins.iter().map(String::as_str).collect();
+    let mut restored = 0;
+
+    restored += pin_entries(
+        result,
+        &existing.clusters,
+        EntryKind::Cluster,
+        |config| &mut config.clusters,
+        |c: &NamedCluster| &c.name,
+        &pins,
+    );
+    restored += pin_entries(
+        result,
+        &existing.contexts,
+        EntryKind::Context,
+        |config| &mut config.contexts,
+        |c: &NamedContext| &c.name,
+        &pins,
+    );
+    restored += pin_entries(
+        result,
+        &existing.users,
+        EntryKind::User,
+        |config| &mut config.users,
+        |u: &NamedUser| &u.name,
+        &pins,
+    );
+
+    for pin in &pins {
+        let found = existing.clusters.iter().flatten().any(|c| c.name == *pin)
+            || existing.contexts.iter().flatten().any(|c| c.name == *pin)
+            || existing.users.iter().flatten().any(|u| u.name == *pin);
+        if !found {
+            warn!("Pinned entry '{}' is not in the existing output", pin);
+        }
+    }
+    if restored > 0 {
+        info!("Kept {} pinned entries from the existing output", restored);
+    }
+}
+
+fn pin_entries<T: Clone>(
+    result: &mut MergeResult,
+    existing: &Option<Vec<T>>,
+    kind: EntryKind,
+    entries: fn(&mut KubeConfig) -> &mut Option<Vec<T>>,
+    name: fn(&T) -> &String,
+    pins: &HashSet<&str>,
+) -> usize {
+    let mut restored = 0;
+    for pinned in existing.iter().flatten() {
+        let pinned_name = name(pinned);
+        if !pins.contains(pinned_name.as_str()) {
+            continue;
+        }
+        let merged = entries(&mut result.config).get_or_insert_with(Vec::new);
+        match merged.iter_mut().find(|entry| name(entry) == pinned_name) {
+            Some(entry) => *entry = pinned.clone(),
+            None => merged.push(pinned.clone()),
+        }
+        if let Some(source) = result.sources.remove(&(kind, pinned_name.clone())) {
+            result.skip(SkippedEntry {
+                kind: Some(kind),
+                name: pinned_name.clone(),
+                source_file: source,
+                reason: SkipReason::Pinned,
+            });
+        }
+        restored += 1;
+    }
+    restored
+}
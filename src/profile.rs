@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use serde_yml::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use tracing::debug;
+
+#[derive(Debug, Default, Deserialize)]
+struct ToolConfig {
+    #[serde(default)]
+    profiles: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+/// Loads a named profile and renders it as command-line arguments. Keys are
+/// long option names; `true` enables a flag, lists repeat the option.
+/// Options named in `given` are left out, as the command line sets them:
+/// repeated options add up rather than override, so a profile's list would
+/// otherwise be extended instead of replaced. The arguments are meant to be
+/// placed before the user's own so that explicit values also win for the
+/// rest.
+pub fn load_profile(
+    config_file: &str,
+    name: &str,
+    given: &[&str],
+) -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(config_file)
+        .map_err(|e| format!("Failed to read config file {}: {}", config_file, e))?;
+    let config: ToolConfig = serde_yml::from_str(&content)
+        .map_err(|e| format!("Failed to parse config file {}: {}", config_file, e))?;
+
+    let profile = config
+        .profiles
+        .get(name)
+        .ok_or_else(|| format!("Profile '{}' not found in {}", name, config_file))?;
+
+    let mut args = Vec::new();
+    for (key, value) in profile {
+        if key == "profile" || key == "config" {
+            return Err(format!("Profile '{}' cannot set '{}'", name, key).into());
+        }
+        if given.contains(&key.as_str()) {
+            debug!("Command line overrides '{}' of profile '{}'", key, name);
+            continue;
+        }
+        push_option(&mut args, key, value)
+            .map_err(|e| format!("Profile '{}', option '{}': {}", name, key, e))?;
+    }
+
+    debug!("Profile '{}' expands to {:?}", name, args);
+    Ok(args)
+}
+
+fn push_option(args: &mut Vec<OsString>, key: &str, value: &Value) -> Result<(), String> {
+    match value {
+        Value::Bool(true) => args.push(format!("--{}", key).into()),
+        Value::Bool(false) | Value::Null => {}
+        Value::String(s) => args.push(format!("--{}={}", key, s).into()),
+        Value::Number(n) => args.push(format!("--{}={}", key, n).into()),
+        Value::Sequence(items) => {
+            for item in items {
+                match item {
+                    Value::Sequence(_) | Value::Mapping(_) => {
+                        return Err("nested lists and maps are not supported".to_string());
+                    }
+                    _ => push_option(args, key, item)?,
+                }
+            }
+        }
+        _ => return Err("unsupported value".to_string()),
+    }
+    Ok(())
+}
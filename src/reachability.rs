@@ -0,0 +1,83 @@
+use crate::config::KubeConfig;
+use crate::merge::{EntryKind, Sources};
+use crate::utils::server_address;
+use crate::validate::{IssueKind, Severity, ValidationIssue};
+use std::collections::BTreeMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Probes every distinct cluster endpoint with a plain TCP connect and
+/// reports the clusters whose endpoint could not be reached. Probes run in
+/// parallel and the whole check is bounded by `timeout`; endpoints that have
+/// not answered by then count as unreachable.
+pub fn check_reachability(
+    config: &KubeConfig,
+    sources: &Sources,
+    timeout: Duration,
+) -> Vec<ValidationIssue> {
+    let mut endpoints: BTreeMap<(String, u16), Vec<&str>> = BTreeMap::new();
+    for cluster in config.clusters.iter().flatten() {
+        match server_address(&cluster.cluster.server) {
+            Some((host, port)) => endpoints
+                .entry((host.to_string(), port))
+                .or_default()
+                .push(&cluster.name),
+            None => debug!(
+                "Cluster '{}' has no usable server address, not probing",
+                cluster.name
+            ),
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    for (host, port) in endpoints.keys().cloned() {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let reachable = probe(&host, port, timeout);
+            let _ = sender.send(((host, port), reachable));
+        });
+    }
+    drop(sender);
+
+    let deadline = Instant::now() + timeout;
+    let mut reachable = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok((endpoint, true)) => reachable.push(endpoint),
+            Ok((_, false)) => {}
+            Err(_) => break,
+        }
+    }
+
+    let mut issues = Vec::new();
+    for ((host, port), clusters) in endpoints {
+        if reachable.contains(&(host.clone(), port)) {
+            debug!("{}:{} is reachable", host, port);
+            continue;
+        }
+        for name in clusters {
+            issues.push(ValidationIssue {
+                severity: Severity::Warning,
+                kind: IssueKind::UnreachableCluster,
+                name: name.to_string(),
+                source: sources
+                    .get(&(EntryKind::Cluster, name.to_string()))
+                    .cloned(),
+                message: format!("Cluster '{}' server {}:{} is unreachable", name, host, port),
+            });
+        }
+    }
+    issues
+}
+
+fn probe(host: &str, port: u16, timeout: Duration) -> bool {
+    let Ok(addresses) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addresses
+        .into_iter()
+        .any(|address| TcpStream::connect_timeout(&address, timeout).is_ok())
+}
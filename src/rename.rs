@@ -0,0 +1,349 @@
+use crate::config::{Cluster, NamedCluster, NamedContext, NamedUser};
+use crate::merge::{EntryKind, MergeResult, SOURCE_ANNOTATION, SkipReason, SkippedEntry};
+use crate::utils::server_host;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{debug, warn};
+
+/// Renames entries of one kind and rewrites every reference to them: context
+/// cluster/user references, `current-context`, and the recorded sources.
+/// Renames are applied simultaneously, so chains and swaps behave as
+/// expected. If two entries end up with the same name, the first is kept.
+pub fn apply_renames(result: &mut MergeResult, kind: EntryKind, renames: &[(String, String)]) {
+    rename_entries(result, kind, renames, SkipReason::RenameCollision);
+}
+
+/// Folds clusters that connect the same way into the first of them, see
+/// [`coalesce_renames`]. The folded clusters are reported as coalesced.
+pub fn coalesce_clusters(result: &mut MergeResult) {
+    let renames = coalesce_renames(result);
+    rename_entries(result, EntryKind::Cluster, &renames, SkipReason::Coalesced);
+}
+
+/// Applies renames, recording entries that land on a taken name as skipped
+/// for `reason`.
+fn rename_entries(
+    result: &mut MergeResult,
+    kind: EntryKind,
+    renames: &[(String, String)],
+    reason: SkipReason,
+) {
+    let map: HashMap<&str, &str> = renames
+        .iter()
+        .filter(|(old, new)| old != new)
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .collect();
+    if map.is_empty() {
+        return;
+    }
+
+    let config = &mut result.config;
+    let mut moved = Vec::new();
+    let mut originals = Vec::new();
+    let mut rename = |name: &mut String| {
+        if let Some(new) = map.get(name.as_str()) {
+            debug!("Renaming {} '{}' to '{}'", kind, name, new);
+            if let Some(source) = result.sources.remove(&(kind, name.clone())) {
+                moved.push((new.to_string(), source));
+            }
+            let original = result
+                .original_names
+                .remove(&(kind, name.clone()))
+                .unwrap_or_else(|| name.clone());
+            originals.push((new.to_string(), original));
+            *name = new.to_string();
+        }
+    };
+
+    match kind {
+        EntryKind::Cluster => {
+            config
+                .clusters
+                .iter_mut()
+                .flatten()
+                .for_each(|c| rename(&mut c.name));
+            for context in config.contexts.iter_mut().flatten() {
+                if let Some(new) = map.get(context.context.cluster.as_str()) {
+                    context.context.cluster = new.to_string();
+                }
+            }
+        }
+        EntryKind::User => {
+            config
+                .users
+                .iter_mut()
+                .flatten()
+                .for_each(|u| rename(&mut u.name));
+            for context in config.contexts.iter_mut().flatten() {
+                if let Some(new) = map.get(context.context.user.as_str()) {
+                    context.context.user = new.to_string();
+                }
+            }
+        }
+        EntryKind::Context => {
+            config
+                .contexts
+                .iter_mut()
+                .flatten()
+                .for_each(|c| rename(&mut c.name));
+            if let Some(new) = map.get(config.current_context.as_str()) {
+                config.current_context = new.to_string();
+            }
+        }
+    }
+
+    for (name, original) in originals {
+        result
+            .original_names
+            .entry((kind, name))
+            .or_insert(original);
+    }
+
+    let mut dropped = Vec::new();
+    for (name, source) in moved {
+        match result.sources.entry((kind, name.clone())) {
+            Entry::Vacant(slot) => {
+                slot.insert(source);
+            }
+            Entry::Occupied(_) => dropped.push(SkippedEntry {
+                kind: Some(kind),
+                name,
+                source_file: source,
+                reason,
+            }),
+        }
+    }
+
+    match kind {
+        EntryKind::Cluster => {
+            drop_duplicates(&mut config.clusters, kind, |c: &NamedCluster| &c.name)
+        }
+        EntryKind::Context => {
+            drop_duplicates(&mut config.contexts, kind, |c: &NamedContext| &c.name)
+        }
+        EntryKind::User => drop_duplicates(&mut config.users, kind, |u: &NamedUser| &u.name),
+    }
+    for entry in dropped {
+        result.skip(entry);
+    }
+}
+
+/// Builds renames that prepend `prefix` to every entry of `kind`.
+pub fn prefix_renames(
+    result: &MergeResult,
+    kind: EntryKind,
+    prefix: &str,
+) -> Vec<(String, String)> {
+    entry_names(result, kind)
+        .into_iter()
+        .map(|name| {
+            let renamed = format!("{}{}", prefix, name);
+            (name, renamed)
+        })
+        .collect()
+}
+
+/// Builds renames that append `@<host>` to every context, where host is taken
+/// from the server URL of the context's cluster. Contexts whose cluster is
+/// missing or has no parsable host keep their name.
+pub fn server_suffix_renames(result: &MergeResult) -> Vec<(String, String)> {
+    let config = &result.config;
+    config
+        .contexts
+        .iter()
+        .flatten()
+        .filter_map(|context| {
+            let host = config
+                .clusters
+                .iter()
+                .flatten()
+                .find(|c| c.name == context.context.cluster)
+                .and_then(|c| server_host(&c.cluster.server));
+            match host {
+                Some(host) => Some((context.name.clone(), format!("{}@{}", context.name, host))),
+                None => {
+                    debug!(
+                        "No server host for context '{}', not suffixing",
+                        context.name
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Builds renames that fold clusters connecting the same way into the first
+/// of them: same server, TLS server name, CA, `insecure-skip-tls-verify`,
+/// `proxy-url` and other connection fields. Only extensions and the
+/// recorded source may differ. Applying them points the contexts of every
+/// duplicate at that cluster and drops the duplicates.
+pub fn coalesce_renames(result: &MergeResult) -> Vec<(String, String)> {
+    let mut canonical: HashMap<Cluster, &str> = HashMap::new();
+    let mut renames = Vec::new();
+    for cluster in result.config.clusters.iter().flatten() {
+        let mut key = cluster.cluster.clone();
+        key.extensions = None;
+        key.other.remove(SOURCE_ANNOTATION);
+        match canonical.entry(key) {
+            Entry::Vacant(slot) => {
+                slot.insert(&cluster.name);
+            }
+            Entry::Occupied(slot) => {
+                warn!(
+                    "Coalescing cluster '{}' into '{}', they connect the same way",
+                    cluster.name,
+                    slot.get()
+                );
+                renames.push((cluster.name.clone(), slot.get().to_string()));
+            }
+        }
+    }
+    renames
+}
+
+/// Placeholders a context template may use.
+const TEMPLATE_PLACEHOLDERS: [&str; 4] = ["name", "cluster", "user", "namespace"];
+
+/// Checks that a context template only uses known placeholders.
+pub fn parse_context_template(template: &str) -> Result<String, String> {
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err(format!("unclosed '{{' in '{}'", template));
+        };
+        let placeholder = &rest[open + 1..open + close];
+        if !TEMPLATE_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!(
+                "unknown placeholder {{{}}}, expected one of {{{}}}",
+                placeholder,
+                TEMPLATE_PLACEHOLDERS.join("}, {")
+            ));
+        }
+        rest = &rest[open + close + 1..];
+    }
+    Ok(template.to_string())
+}
+
+/// Builds renames that name every context after `template`, filling in
+/// `{name}`, `{cluster}`, `{user}`, and `{namespace}`; for contexts without a
+/// namespace, `{namespace}` is replaced by the `default` argument. A context
+/// whose name would be empty keeps its name. Contexts that would end up with
+/// the same name get `-2`, `-3`, ... appended.
+pub fn template_renames(
+    result: &MergeResult,
+    template: &str,
+    default: &str,
+) -> Vec<(String, String)> {
+    let mut taken = HashSet::new();
+    let mut renames = Vec::new();
+    for context in result.config.contexts.iter().flatten() {
+        let mut rendered = render_context_template(template, context, default);
+        if rendered.is_empty() {
+            warn!(
+                "Context template gives context '{}' an empty name, keeping it",
+                context.name
+            );
+            rendered = context.name.clone();
+        }
+
+        let mut renamed = rendered.clone();
+        let mut n = 2;
+        while !taken.insert(renamed.clone()) {
+            renamed = format!("{}-{}", rendered, n);
+            n += 1;
+        }
+        if renamed != rendered {
+            warn!(
+                "Context template name '{}' is already taken, naming context '{}' '{}'",
+                rendered, context.name, renamed
+            );
+        }
+        if renamed != context.name {
+            renames.push((context.name.clone(), renamed));
+        }
+    }
+    renames
+}
+
+/// Fills in the placeholders of a context template in one pass, so braces
+/// in the substituted names are left as they are.
+fn render_context_template(template: &str, context: &NamedContext, default: &str) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        rendered.push_str(&rest[..open]);
+        match &rest[open + 1..close] {
+            "name" => rendered.push_str(&context.name),
+            "cluster" => rendered.push_str(&context.context.cluster),
+            "user" => rendered.push_str(&context.context.user),
+            "namespace" => {
+                rendered.push_str(context.context.namespace.as_deref().unwrap_or(default))
+            }
+            _ => rendered.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Maps the original name of every renamed context to its current name,
+/// for use as short aliases.
+pub fn context_aliases(result: &MergeResult) -> BTreeMap<String, String> {
+    entry_names(result, EntryKind::Context)
+        .into_iter()
+        .filter_map(|name| {
+            let original = result
+                .original_names
+                .get(&(EntryKind::Context, name.clone()))?;
+            (original != &name).then(|| (original.clone(), name))
+        })
+        .collect()
+}
+
+pub fn entry_names(result: &MergeResult, kind: EntryKind) -> Vec<String> {
+    let config = &result.config;
+    match kind {
+        EntryKind::Cluster => config
+            .clusters
+            .iter()
+            .flatten()
+            .map(|c| c.name.clone())
+            .collect(),
+        EntryKind::Context => config
+            .contexts
+            .iter()
+            .flatten()
+            .map(|c| c.name.clone())
+            .collect(),
+        EntryKind::User => config
+            .users
+            .iter()
+            .flatten()
+            .map(|u| u.name.clone())
+            .collect(),
+    }
+}
+
+fn drop_duplicates<T>(entries: &mut Option<Vec<T>>, kind: EntryKind, name: fn(&T) -> &String) {
+    let Some(list) = entries else {
+        return;
+    };
+
+    let mut seen = HashSet::new();
+    list.retain(|entry| {
+        let keep = seen.insert(name(entry).clone());
+        if !keep {
+            warn!(
+                "Renaming produced duplicate {} '{}', keeping the first",
+                kind,
+                name(entry)
+            );
+        }
+        keep
+    });
+}
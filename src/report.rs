@@ -0,0 +1,221 @@
+use crate::config::KubeConfig;
+use crate::merge::{MergeResult, SkippedEntry};
+use crate::style;
+use crate::validate::{Severity, ValidationIssue};
+use chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use tracing::info;
+
+#[derive(Debug, Serialize)]
+pub struct MergeStats {
+    pub result: &'static str,
+    pub files: usize,
+    pub clusters: usize,
+    pub contexts: usize,
+    pub users: usize,
+    pub backup: Option<String>,
+    pub warnings: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unreachable: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Default for MergeStats {
+    fn default() -> Self {
+        MergeStats {
+            result: "ok",
+            files: 0,
+            clusters: 0,
+            contexts: 0,
+            users: 0,
+            backup: None,
+            warnings: 0,
+            unreachable: None,
+            error: None,
+        }
+    }
+}
+
+impl MergeStats {
+    pub fn record(&mut self, config: &KubeConfig) {
+        self.clusters = config.clusters.as_ref().map(|c| c.len()).unwrap_or(0);
+        self.contexts = config.contexts.as_ref().map(|c| c.len()).unwrap_or(0);
+        self.users = config.users.as_ref().map(|u| u.len()).unwrap_or(0);
+    }
+
+    pub fn fail(&mut self, error: String) {
+        self.result = "failed";
+        self.error = Some(error);
+    }
+
+    /// Renders the stats as a single `key=value` line for log scraping.
+    pub fn summary_line(&self) -> String {
+        let mut line = format!(
+            "kubemerge: result={} files={} clusters={} contexts={} users={} backup={} warnings={}",
+            self.result,
+            self.files,
+            self.clusters,
+            self.contexts,
+            self.users,
+            self.backup.as_deref().unwrap_or("none"),
+            self.warnings
+        );
+        if let Some(unreachable) = self.unreachable {
+            line.push_str(&format!(" unreachable={}", unreachable));
+        }
+        if let Some(error) = &self.error {
+            line.push_str(&format!(" error={:?}", error));
+        }
+        line
+    }
+}
+
+pub fn print_summary_line(
+    stats: &MergeStats,
+    format: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        "json" => eprintln!("{}", serde_json::to_string(stats)?),
+        _ if stats.error.is_some() => eprintln!("{}", style::error(stats.summary_line())),
+        _ => eprintln!("{}", style::success(stats.summary_line())),
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct MergeRecord<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    stats: &'a MergeStats,
+}
+
+/// Appends the stats of this run as one JSON line to `path`, creating the
+/// file if needed.
+pub fn append_merge_report(path: &Path, stats: &MergeStats) -> Result<(), String> {
+    let record = MergeRecord {
+        timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        stats,
+    };
+    let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// Overwrites `path` with the stats of this run as pretty JSON. The file is
+/// written beside `path` and renamed into place, so a dashboard polling it
+/// never reads a half-written status.
+pub fn write_status_file(path: &Path, stats: &MergeStats) -> Result<(), String> {
+    let record = MergeRecord {
+        timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        stats,
+    };
+    let content = serde_json::to_string_pretty(&record).map_err(|e| e.to_string())?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content + "\n").map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        e.to_string()
+    })
+}
+
+/// Reports entries and files left out of the merge. JSON goes to stdout so
+/// it can be piped; the text form is logged.
+pub fn print_skipped(
+    skipped: &[SkippedEntry],
+    format: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(skipped)?),
+        _ if skipped.is_empty() => {}
+        _ => {
+            info!("Skipped {} entries:", skipped.len());
+            for entry in skipped {
+                info!("  - {}", entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Renders the merge summary, where each entry came from, skipped entries,
+/// and validation issues as Markdown, ready to paste into a PR or chat.
+pub fn render_markdown(result: &MergeResult, files: usize, issues: &[ValidationIssue]) -> String {
+    let config = &result.config;
+    let mut out = String::from("## kubemerge report\n\n");
+
+    out.push_str("| Files | Clusters | Contexts | Users | Current context |\n");
+    out.push_str("| ---: | ---: | ---: | ---: | --- |\n");
+    out.push_str(&format!(
+        "| {} | {} | {} | {} | {} |\n",
+        files,
+        config.clusters.as_ref().map_or(0, Vec::len),
+        config.contexts.as_ref().map_or(0, Vec::len),
+        config.users.as_ref().map_or(0, Vec::len),
+        markdown_cell(if config.current_context.is_empty() {
+            "none"
+        } else {
+            &config.current_context
+        }),
+    ));
+
+    let mut sources: Vec<_> = result.sources.iter().collect();
+    sources.sort_by(|a, b| a.0.cmp(b.0));
+    if !sources.is_empty() {
+        out.push_str("\n### Sources\n\n| Kind | Name | Source |\n| --- | --- | --- |\n");
+        for ((kind, name), source) in sources {
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                kind,
+                markdown_cell(name),
+                markdown_cell(&source.display().to_string())
+            ));
+        }
+    }
+
+    if !result.skipped.is_empty() {
+        out.push_str(
+            "\n### Skipped\n\n| Kind | Name | Source | Reason |\n| --- | --- | --- | --- |\n",
+        );
+        for entry in &result.skipped {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                entry
+                    .kind
+                    .map_or("file".to_string(), |kind| kind.to_string()),
+                markdown_cell(&entry.name),
+                markdown_cell(&entry.source_file.display().to_string()),
+                entry.reason
+            ));
+        }
+    }
+
+    if !issues.is_empty() {
+        out.push_str("\n### Issues\n\n| Severity | Kind | Message |\n| --- | --- | --- |\n");
+        for issue in issues {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} |\n",
+                severity,
+                issue.kind,
+                markdown_cell(&issue.to_string())
+            ));
+        }
+    }
+    out
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,44 @@
+use crate::config::KubeConfig;
+use crate::validate::{IssueKind, Severity, ValidationIssue};
+use jsonschema::Validator;
+use std::fs;
+use std::path::Path;
+
+/// Reads and compiles a JSON Schema. Failures here are about the schema
+/// itself, not about the config it is meant to check.
+pub fn load_schema(path: &Path) -> Result<Validator, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read schema {}: {}", path.display(), e))?;
+    let schema: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse schema {}: {}", path.display(), e))?;
+    Ok(jsonschema::validator_for(&schema)
+        .map_err(|e| format!("Invalid schema {}: {}", path.display(), e))?)
+}
+
+/// Validates the config, as JSON, against a schema and reports every
+/// violation as an error named after the offending location, such as
+/// `/users/0/user`. Offending values are masked in the message since they
+/// are often credentials.
+pub fn check_schema(
+    config: &KubeConfig,
+    validator: &Validator,
+) -> Result<Vec<ValidationIssue>, Box<dyn std::error::Error>> {
+    let instance = serde_json::to_value(config)
+        .map_err(|e| format!("Failed to convert merged config to JSON: {}", e))?;
+    Ok(validator
+        .iter_errors(&instance)
+        .map(|violation| {
+            let location = match violation.instance_path().as_str() {
+                "" => "/".to_string(),
+                path => path.to_string(),
+            };
+            ValidationIssue {
+                severity: Severity::Error,
+                kind: IssueKind::SchemaViolation,
+                message: format!("Schema violation at {}: {}", location, violation.masked()),
+                name: location,
+                source: None,
+            }
+        })
+        .collect())
+}
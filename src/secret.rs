@@ -0,0 +1,46 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_yml::Value;
+
+/// Keys a Secret may hold its kubeconfig under, in lookup order. `value` is
+/// what Cluster API uses for its generated kubeconfig secrets.
+const KUBECONFIG_KEYS: [&str; 4] = ["config", "kubeconfig", "kubeconfig.yaml", "value"];
+
+/// Returns the kubeconfig embedded in a Kubernetes Secret manifest, taken
+/// from `stringData` as-is or from `data` base64-decoded. Returns `Ok(None)`
+/// when the document is not a Secret.
+pub fn secret_kubeconfig(content: &str) -> Result<Option<String>, String> {
+    let Ok(document) = serde_yml::from_str::<Value>(content) else {
+        return Ok(None);
+    };
+    if document.get("kind").and_then(Value::as_str) != Some("Secret") {
+        return Ok(None);
+    }
+
+    for key in KUBECONFIG_KEYS {
+        if let Some(value) = document
+            .get("stringData")
+            .and_then(|data| data.get(key))
+            .and_then(Value::as_str)
+        {
+            return Ok(Some(value.to_string()));
+        }
+        if let Some(value) = document
+            .get("data")
+            .and_then(|data| data.get(key))
+            .and_then(Value::as_str)
+        {
+            let decoded = STANDARD
+                .decode(value.trim())
+                .map_err(|e| format!("data.{} is not valid base64: {}", key, e))?;
+            return String::from_utf8(decoded)
+                .map(Some)
+                .map_err(|e| format!("data.{} is not UTF-8: {}", key, e));
+        }
+    }
+
+    Err(format!(
+        "Secret has no kubeconfig under data or stringData (looked for {})",
+        KUBECONFIG_KEYS.join(", ")
+    ))
+}
@@ -0,0 +1,284 @@
+use crate::certs::{certificate_expiry, load_certificate};
+use crate::merge::{EntryKind, MergeResult, SkipReason, SkippedEntry};
+use crate::metadata::METADATA_PREFIX;
+use crate::utils::server_address;
+use chrono::Utc;
+use serde_yml::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// An annotation `key=value` that selected entries must carry.
+pub type Selector = (String, String);
+
+/// Parses a `key=value` selector.
+pub fn parse_selector(value: &str) -> Result<Selector, String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+    }
+}
+
+/// How an entry's annotations relate to a set of selectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Matches,
+    /// Carries none of the selected keys.
+    Unannotated,
+    /// Carries a selected key with another value.
+    Conflicts,
+}
+
+/// Keeps the contexts whose annotations match every selector, plus the
+/// clusters and users that match or that a kept context uses. A key matches
+/// both as written and under the `kubemerge.io/` prefix, so `team=platform`
+/// selects metadata added with `--metadata-file`. Dropped entries are
+/// recorded as skipped.
+pub fn select_entries(result: &mut MergeResult, selectors: &[Selector]) {
+    let config = &mut result.config;
+    let mut dropped = Vec::new();
+
+    if let Some(contexts) = &mut config.contexts {
+        contexts.retain(|context| {
+            let keep = selection(&context.context.other, selectors) == Selection::Matches;
+            if !keep {
+                dropped.push((EntryKind::Context, context.name.clone()));
+            }
+            keep
+        });
+    }
+    let contexts: Vec<_> = config.contexts.iter().flatten().collect();
+    let used_clusters: HashSet<&str> = contexts
+        .iter()
+        .map(|c| c.context.cluster.as_str())
+        .collect();
+    let used_users: HashSet<&str> = contexts.iter().map(|c| c.context.user.as_str()).collect();
+
+    let had_clusters = config.clusters.is_some();
+    let mut kept_clusters = Vec::new();
+    for cluster in config.clusters.take().into_iter().flatten() {
+        let used = used_clusters.contains(cluster.name.as_str());
+        match selection(&cluster.cluster.other, selectors) {
+            Selection::Matches => kept_clusters.push(cluster),
+            Selection::Unannotated if used => kept_clusters.push(cluster),
+            found => {
+                if used && found == Selection::Conflicts {
+                    warn!(
+                        "Cluster '{}' does not match the selection, leaving the contexts that use it dangling",
+                        cluster.name
+                    );
+                }
+                dropped.push((EntryKind::Cluster, cluster.name));
+            }
+        }
+    }
+    let had_users = config.users.is_some();
+    let mut kept_users = Vec::new();
+    for user in config.users.take().into_iter().flatten() {
+        let used = used_users.contains(user.name.as_str());
+        match selection(&user.user.other, selectors) {
+            Selection::Matches => kept_users.push(user),
+            Selection::Unannotated if used => kept_users.push(user),
+            found => {
+                if used && found == Selection::Conflicts {
+                    warn!(
+                        "User '{}' does not match the selection, leaving the contexts that use it dangling",
+                        user.name
+                    );
+                }
+                dropped.push((EntryKind::User, user.name));
+            }
+        }
+    }
+    config.clusters = had_clusters.then_some(kept_clusters);
+    config.users = had_users.then_some(kept_users);
+
+    if !config.current_context.is_empty()
+        && dropped.contains(&(EntryKind::Context, config.current_context.clone()))
+    {
+        warn!(
+            "Current context '{}' is not selected, clearing it",
+            config.current_context
+        );
+        config.current_context.clear();
+    }
+
+    info!("Selection dropped {} entries", dropped.len());
+    for (kind, name) in dropped {
+        if let Some(source) = result.sources.remove(&(kind, name.clone())) {
+            result.skip(SkippedEntry {
+                kind: Some(kind),
+                name,
+                source_file: source,
+                reason: SkipReason::NotSelected,
+            });
+        }
+    }
+}
+
+/// Reads a server allowlist: one host per line, with blank lines and `#`
+/// comments ignored. Hosts are compared case-insensitively.
+pub fn load_allowed_servers(path: &Path) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read server allowlist {}: {}", path.display(), e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Drops clusters whose server host is not on the allowlist, the contexts
+/// that use them, and the users only those contexts used. Dropped entries
+/// are recorded as skipped.
+pub fn allow_servers(result: &mut MergeResult, allowed: &HashSet<String>) {
+    let config = &mut result.config;
+    let mut dropped = Vec::new();
+
+    if let Some(clusters) = &mut config.clusters {
+        clusters.retain(|cluster| {
+            let host = server_address(&cluster.cluster.server).map(|(host, _)| host);
+            let keep = host.is_some_and(|host| allowed.contains(&host.to_lowercase()));
+            if !keep {
+                warn!(
+                    "Skipping cluster '{}', server {} is not on the allowlist",
+                    cluster.name, cluster.cluster.server
+                );
+                dropped.push((EntryKind::Cluster, cluster.name.clone()));
+            }
+            keep
+        });
+    }
+
+    let mut dropped_users = HashSet::new();
+    if let Some(contexts) = &mut config.contexts {
+        contexts.retain(|context| {
+            let keep = !dropped.contains(&(EntryKind::Cluster, context.context.cluster.clone()));
+            if !keep {
+                warn!(
+                    "Skipping context '{}', its cluster '{}' is not allowed",
+                    context.name, context.context.cluster
+                );
+                dropped.push((EntryKind::Context, context.name.clone()));
+                dropped_users.insert(context.context.user.clone());
+            }
+            keep
+        });
+    }
+    let used_users: HashSet<&str> = config
+        .contexts
+        .iter()
+        .flatten()
+        .map(|c| c.context.user.as_str())
+        .collect();
+    if let Some(users) = &mut config.users {
+        users.retain(|user| {
+            let keep =
+                used_users.contains(user.name.as_str()) || !dropped_users.contains(&user.name);
+            if !keep {
+                dropped.push((EntryKind::User, user.name.clone()));
+            }
+            keep
+        });
+    }
+
+    if dropped.contains(&(EntryKind::Context, config.current_context.clone())) {
+        warn!(
+            "Current context '{}' uses a cluster that is not allowed, clearing it",
+            config.current_context
+        );
+        config.current_context.clear();
+    }
+
+    if !dropped.is_empty() {
+        info!("Server allowlist dropped {} entries", dropped.len());
+    }
+    for (kind, name) in dropped {
+        if let Some(source) = result.sources.remove(&(kind, name.clone())) {
+            result.skip(SkippedEntry {
+                kind: Some(kind),
+                name,
+                source_file: source,
+                reason: SkipReason::ServerNotAllowed,
+            });
+        }
+    }
+}
+
+/// Removes users whose client certificate has already expired, recording
+/// them as skipped. Users whose certificate cannot be read are kept, and
+/// contexts left pointing at a removed user are reported.
+pub fn drop_expired_users(result: &mut MergeResult) {
+    let now = Utc::now();
+    let mut dropped = Vec::new();
+    if let Some(users) = &mut result.config.users {
+        users.retain(|user| {
+            let certificate = load_certificate(
+                user.user.client_certificate_data.as_deref(),
+                user.user.client_certificate.as_deref(),
+            );
+            let expiry =
+                certificate.and_then(|bytes| bytes.map(|b| certificate_expiry(&b)).transpose());
+            match expiry {
+                Ok(Some(expiry)) if expiry <= now => {
+                    warn!(
+                        "Dropping user '{}', its certificate expired on {}",
+                        user.name,
+                        expiry.format("%Y-%m-%d")
+                    );
+                    dropped.push(user.name.clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    for context in result.config.contexts.iter().flatten() {
+        if dropped.contains(&context.context.user) {
+            warn!(
+                "Context '{}' refers to dropped user '{}'",
+                context.name, context.context.user
+            );
+        }
+    }
+    if !dropped.is_empty() {
+        info!("Dropped {} users with expired certificates", dropped.len());
+    }
+    for name in dropped {
+        if let Some(source) = result.sources.remove(&(EntryKind::User, name.clone())) {
+            result.skip(SkippedEntry {
+                kind: Some(EntryKind::User),
+                name,
+                source_file: source,
+                reason: SkipReason::Expired,
+            });
+        }
+    }
+}
+
+fn selection(annotations: &BTreeMap<String, Value>, selectors: &[Selector]) -> Selection {
+    let mut result = Selection::Matches;
+    for (key, expected) in selectors {
+        let value = annotations
+            .get(key)
+            .or_else(|| annotations.get(&format!("{}{}", METADATA_PREFIX, key)));
+        match value {
+            Some(value) if scalar_string(value).as_deref() == Some(expected) => {}
+            Some(_) => return Selection::Conflicts,
+            None => result = Selection::Unannotated,
+        }
+    }
+    result
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
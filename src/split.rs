@@ -0,0 +1,316 @@
+use crate::config::KubeConfig;
+use crate::merge::{EntryKind, MergeResult, SkipReason, SkippedEntry};
+use crate::rename::entry_names;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tracing::info;
+
+/// Builds a self-contained config holding the named contexts together with
+/// the clusters and users they reference. Extra clusters can be kept even
+/// when no selected context points at them.
+pub fn extract_subset(
+    config: &KubeConfig,
+    context_names: &[&str],
+    extra_clusters: &[&str],
+) -> KubeConfig {
+    let contexts: Vec<_> = config
+        .contexts
+        .iter()
+        .flatten()
+        .filter(|c| context_names.contains(&c.name.as_str()))
+        .cloned()
+        .collect();
+    let cluster_names: HashSet<&str> = contexts
+        .iter()
+        .map(|c| c.context.cluster.as_str())
+        .chain(extra_clusters.iter().copied())
+        .collect();
+    let user_names: HashSet<&str> = contexts.iter().map(|c| c.context.user.as_str()).collect();
+
+    let clusters: Vec<_> = config
+        .clusters
+        .iter()
+        .flatten()
+        .filter(|c| cluster_names.contains(c.name.as_str()))
+        .cloned()
+        .collect();
+    let users: Vec<_> = config
+        .users
+        .iter()
+        .flatten()
+        .filter(|u| user_names.contains(u.name.as_str()))
+        .cloned()
+        .collect();
+
+    let current_context = if context_names.contains(&config.current_context.as_str()) {
+        config.current_context.clone()
+    } else {
+        context_names
+            .first()
+            .map(|c| c.to_string())
+            .unwrap_or_default()
+    };
+
+    KubeConfig {
+        api_version: config.api_version.clone(),
+        kind: config.kind.clone(),
+        clusters: (!clusters.is_empty()).then_some(clusters),
+        contexts: (!contexts.is_empty()).then_some(contexts),
+        users: (!users.is_empty()).then_some(users),
+        current_context,
+        preferences: config.preferences.clone(),
+        other: config.other.clone(),
+    }
+}
+
+/// Drops clusters and users that no context references, like
+/// `kubectl config view --minify` applied to every context. Dropped entries
+/// are recorded as pruned.
+pub fn minify(result: &mut MergeResult) {
+    let context_names = entry_names(result, EntryKind::Context);
+    let context_names: Vec<&str> = context_names.iter().map(String::as_str).collect();
+    let mut minified = extract_subset(&result.config, &context_names, &[]);
+    minified.current_context = result.config.current_context.clone();
+    let original = std::mem::replace(&mut result.config, minified);
+
+    for kind in [EntryKind::Cluster, EntryKind::User] {
+        let kept = entry_names(result, kind);
+        let all: Vec<&String> = match kind {
+            EntryKind::Cluster => original
+                .clusters
+                .iter()
+                .flatten()
+                .map(|c| &c.name)
+                .collect(),
+            _ => original.users.iter().flatten().map(|u| &u.name).collect(),
+        };
+        for name in all.into_iter().filter(|name| !kept.contains(name)) {
+            info!("Dropping unreferenced {} '{}'", kind, name);
+            if let Some(source) = result.sources.remove(&(kind, name.clone())) {
+                result.skip(SkippedEntry {
+                    kind: Some(kind),
+                    name: name.clone(),
+                    source_file: source,
+                    reason: SkipReason::Pruned,
+                });
+            }
+        }
+    }
+}
+
+/// Splits the config into one kubeconfig per context, or per cluster when
+/// `by_cluster` is set, each keyed by its path in `dir`. Nothing is written;
+/// see [`write_parts`].
+pub fn split_config(
+    config: &KubeConfig,
+    dir: &Path,
+    by_cluster: bool,
+) -> Vec<(PathBuf, KubeConfig)> {
+    let parts: Vec<(String, KubeConfig)> = if by_cluster {
+        config
+            .clusters
+            .iter()
+            .flatten()
+            .map(|cluster| {
+                let context_names: Vec<&str> = config
+                    .contexts
+                    .iter()
+                    .flatten()
+                    .filter(|c| c.context.cluster == cluster.name)
+                    .map(|c| c.name.as_str())
+                    .collect();
+                (
+                    cluster.name.clone(),
+                    extract_subset(config, &context_names, &[cluster.name.as_str()]),
+                )
+            })
+            .collect()
+    } else {
+        config
+            .contexts
+            .iter()
+            .flatten()
+            .map(|context| {
+                (
+                    context.name.clone(),
+                    extract_subset(config, &[context.name.as_str()], &[]),
+                )
+            })
+            .collect()
+    };
+
+    let mut used_names = HashSet::new();
+    parts
+        .into_iter()
+        .map(|(name, part)| {
+            let base = sanitize_file_name(&name);
+            let mut file_name = format!("{}.yaml", base);
+            let mut suffix = 2;
+            while !used_names.insert(file_name.clone()) {
+                file_name = format!("{}-{}.yaml", base, suffix);
+                suffix += 1;
+            }
+            (dir.join(file_name), part)
+        })
+        .collect()
+}
+
+/// Splits the config into partial kubeconfigs that `KUBECONFIG` can stitch
+/// back together: `clusters.yaml`, `contexts.yaml`, `users.yaml`, and
+/// `current-context.yaml`, which also carries preferences and any other
+/// top-level keys. Nothing is written; see [`write_parts`].
+pub fn split_sections(config: &KubeConfig, dir: &Path) -> Vec<(PathBuf, KubeConfig)> {
+    let empty = KubeConfig {
+        api_version: config.api_version.clone(),
+        kind: config.kind.clone(),
+        clusters: None,
+        contexts: None,
+        users: None,
+        current_context: String::new(),
+        preferences: BTreeMap::new(),
+        other: BTreeMap::new(),
+    };
+    let sections = [
+        (
+            "clusters.yaml",
+            KubeConfig {
+                clusters: config.clusters.clone(),
+                ..empty.clone()
+            },
+        ),
+        (
+            "contexts.yaml",
+            KubeConfig {
+                contexts: config.contexts.clone(),
+                ..empty.clone()
+            },
+        ),
+        (
+            "users.yaml",
+            KubeConfig {
+                users: config.users.clone(),
+                ..empty.clone()
+            },
+        ),
+        (
+            "current-context.yaml",
+            KubeConfig {
+                current_context: config.current_context.clone(),
+                preferences: config.preferences.clone(),
+                other: config.other.clone(),
+                ..empty
+            },
+        ),
+    ];
+
+    sections
+        .into_iter()
+        .map(|(file_name, section)| (dir.join(file_name), section))
+        .collect()
+}
+
+/// Writes the kubeconfigs [`split_config`] or [`split_sections`] produced,
+/// creating their directories as needed.
+pub fn write_parts(parts: &[(PathBuf, KubeConfig)]) -> Result<(), Box<dyn std::error::Error>> {
+    for (path, part) in parts {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_yml::to_string(part)?)?;
+        info!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Matches the `{1}` and `{env}` placeholders of an output template.
+static GROUP_PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// Partitions the config into groups of contexts. Each context name is
+/// searched with `pattern` and goes to the path `template` names with the
+/// captures filled in, such as `{1}-config.yaml` or `{env}-config.yaml`.
+/// Every group holds its contexts with the clusters and users they
+/// reference. Returns the groups by path and the config of the contexts the
+/// pattern does not match, which is written to the regular output.
+pub fn partition_by_group(
+    config: &KubeConfig,
+    pattern: &Regex,
+    template: &str,
+) -> (Vec<(PathBuf, KubeConfig)>, KubeConfig) {
+    let mut groups: BTreeMap<PathBuf, Vec<&str>> = BTreeMap::new();
+    let mut unmatched = Vec::new();
+    for context in config.contexts.iter().flatten() {
+        match pattern.captures(&context.name) {
+            Some(captures) => groups
+                .entry(PathBuf::from(expand_group_template(template, &captures)))
+                .or_default()
+                .push(&context.name),
+            None => unmatched.push(context.name.as_str()),
+        }
+    }
+
+    let groups = groups
+        .into_iter()
+        .map(|(path, context_names)| (path, extract_subset(config, &context_names, &[])))
+        .collect();
+    (groups, extract_subset(config, &unmatched, &[]))
+}
+
+/// Checks that an output template has placeholders and that each names a
+/// group `pattern` captures.
+pub fn check_group_template(template: &str, pattern: &Regex) -> Result<(), String> {
+    let groups: Vec<&str> = GROUP_PLACEHOLDER
+        .captures_iter(template)
+        .map(|captures| captures.get(1).unwrap().as_str())
+        .collect();
+    if groups.is_empty() {
+        return Err(format!(
+            "Output template '{}' has no placeholder such as {{1}}, so every group would share one file",
+            template
+        ));
+    }
+    for group in groups {
+        let captured = match group.parse::<usize>() {
+            Ok(index) => index > 0 && index < pattern.captures_len(),
+            Err(_) => pattern.capture_names().flatten().any(|name| name == group),
+        };
+        if !captured {
+            return Err(format!(
+                "Output template refers to {{{}}}, which the group pattern '{}' does not capture",
+                group, pattern
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fills each `{group}` placeholder with the capture of that number or name,
+/// made safe for a file name.
+fn expand_group_template(template: &str, captures: &Captures) -> String {
+    GROUP_PLACEHOLDER
+        .replace_all(template, |placeholder: &Captures| {
+            let group = &placeholder[1];
+            let capture = match group.parse::<usize>() {
+                Ok(index) => captures.get(index),
+                Err(_) => captures.name(group),
+            };
+            sanitize_file_name(capture.map(|c| c.as_str()).unwrap_or_default())
+        })
+        .into_owned()
+}
+
+/// Replaces characters that are awkward in file names, such as the slashes
+/// and colons found in cloud provider ARNs.
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,49 @@
+use owo_colors::OwoColorize;
+use std::env;
+use std::io::{IsTerminal, stderr};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Resolves a `--color` choice: `auto` colors only when stderr is a
+/// terminal and `NO_COLOR` is not set.
+pub fn color_enabled(choice: &str) -> bool {
+    match choice {
+        "always" => true,
+        "never" => false,
+        _ => stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// Turns styling of human-facing messages on or off for the process.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+pub fn error(message: impl std::fmt::Display) -> String {
+    if is_color() {
+        message.red().to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+pub fn warning(message: impl std::fmt::Display) -> String {
+    if is_color() {
+        message.yellow().to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+pub fn success(message: impl std::fmt::Display) -> String {
+    if is_color() {
+        message.green().to_string()
+    } else {
+        message.to_string()
+    }
+}
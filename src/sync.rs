@@ -0,0 +1,164 @@
+use crate::config::{KubeConfig, NamedCluster, NamedContext, NamedUser};
+use crate::merge::{EntryKind, MergeResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Entries a previous `--sync` run wrote to the output, with the file each
+/// came from.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    pub entries: Vec<ManagedEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagedEntry {
+    pub kind: EntryKind,
+    pub name: String,
+    pub source: PathBuf,
+}
+
+/// What reconciling the output against the previous state changed.
+#[derive(Debug, Default)]
+pub struct SyncChanges {
+    /// Entries not written by the previous run.
+    pub added: usize,
+    /// Entries of the previous run that no source provides any more.
+    pub removed: usize,
+    /// Entries in the output that kubemerge never managed, kept as they are.
+    pub preserved: usize,
+}
+
+impl SyncState {
+    /// Loads the state file, or an empty state when it does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            debug!("No sync state at {}, starting fresh", path.display());
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read state file {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse state file {}: {}", path.display(), e))?)
+    }
+
+    /// Records the entries of a merge result as managed.
+    pub fn from_result(result: &MergeResult) -> Self {
+        let mut entries: Vec<ManagedEntry> = result
+            .sources
+            .iter()
+            .map(|((kind, name), source)| ManagedEntry {
+                kind: *kind,
+                name: name.clone(),
+                source: source.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        Self { entries }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content + "\n")
+            .map_err(|e| format!("Failed to write state file {}: {}", path.display(), e))?;
+        Ok(())
+    }
+}
+
+/// Reconciles a fresh merge with the existing output. Entries the previous
+/// run wrote but no source provides any more are dropped, while entries
+/// that were added to the output by other means are carried over.
+pub fn reconcile(
+    result: &mut MergeResult,
+    existing: &KubeConfig,
+    state: &SyncState,
+) -> SyncChanges {
+    let managed: HashMap<(EntryKind, &str), &Path> = state
+        .entries
+        .iter()
+        .map(|entry| ((entry.kind, entry.name.as_str()), entry.source.as_path()))
+        .collect();
+    let mut changes = SyncChanges::default();
+
+    reconcile_entries(
+        &mut result.config.clusters,
+        &existing.clusters,
+        EntryKind::Cluster,
+        |c: &NamedCluster| &c.name,
+        &managed,
+        &mut changes,
+    );
+    reconcile_entries(
+        &mut result.config.contexts,
+        &existing.contexts,
+        EntryKind::Context,
+        |c: &NamedContext| &c.name,
+        &managed,
+        &mut changes,
+    );
+    reconcile_entries(
+        &mut result.config.users,
+        &existing.users,
+        EntryKind::User,
+        |u: &NamedUser| &u.name,
+        &managed,
+        &mut changes,
+    );
+
+    let config = &mut result.config;
+    if config.current_context.is_empty()
+        && !existing.current_context.is_empty()
+        && config
+            .contexts
+            .iter()
+            .flatten()
+            .any(|context| context.name == existing.current_context)
+    {
+        debug!(
+            "Keeping current-context '{}' from the existing output",
+            existing.current_context
+        );
+        config.current_context = existing.current_context.clone();
+    }
+    changes
+}
+
+fn reconcile_entries<T: Clone>(
+    merged: &mut Option<Vec<T>>,
+    existing: &Option<Vec<T>>,
+    kind: EntryKind,
+    name: fn(&T) -> &String,
+    managed: &HashMap<(EntryKind, &str), &Path>,
+    changes: &mut SyncChanges,
+) {
+    let merged_names: HashSet<String> = merged.iter().flatten().map(|e| name(e).clone()).collect();
+    changes.added += merged_names
+        .iter()
+        .filter(|n| !managed.contains_key(&(kind, n.as_str())))
+        .count();
+
+    for entry in existing.iter().flatten() {
+        let entry_name = name(entry);
+        if merged_names.contains(entry_name) {
+            continue;
+        }
+        match managed.get(&(kind, entry_name.as_str())) {
+            Some(source) => {
+                info!(
+                    "Removing {} '{}', {} no longer provides it",
+                    kind,
+                    entry_name,
+                    source.display()
+                );
+                changes.removed += 1;
+            }
+            None => {
+                debug!("Keeping unmanaged {} '{}'", kind, entry_name);
+                merged.get_or_insert_with(Vec::new).push(entry.clone());
+                changes.preserved += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::config::User;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use serde_yml::Value;
+
+/// When a user's cached token expires, if it says. Auth providers record it
+/// in their config as `expiry` (RFC 3339, as `gcp` and `oidc` helpers write
+/// it) or `expires-on` (Unix seconds, as `azure` writes it); failing that,
+/// the `exp` claim of an OIDC `id-token` is used. Otherwise a bearer `token`
+/// that is a JWT, such as a service account token, gives its `exp` claim.
+/// `exec` plugins fetch their tokens at run time and never write them to the
+/// kubeconfig, so users relying on one alone have no expiry.
+pub fn token_expiry(user: &User) -> Option<DateTime<Utc>> {
+    if let Some(provider) = &user.auth_provider {
+        let config = &provider.config;
+        if let Some(expiry) = config.get("expiry").and_then(Value::as_str) {
+            return DateTime::parse_from_rfc3339(expiry.trim())
+                .ok()
+                .map(|expiry| expiry.to_utc());
+        }
+        if let Some(expires_on) = config.get("expires-on") {
+            let seconds = match expires_on {
+                Value::String(seconds) => seconds.trim().parse().ok(),
+                other => other.as_i64(),
+            };
+            return seconds.and_then(|seconds| DateTime::from_timestamp(seconds, 0));
+        }
+        if let Some(id_token) = config.get("id-token").and_then(Value::as_str) {
+            return jwt_expiry(id_token);
+        }
+    }
+    user.token.as_deref().and_then(jwt_expiry)
+}
+
+/// Reads the `exp` claim of a JWT without verifying its signature.
+fn jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    DateTime::from_timestamp(claims.get("exp")?.as_i64()?, 0)
+}
@@ -0,0 +1,107 @@
+use crate::config::{NamedCluster, NamedContext, NamedUser};
+use crate::merge::{EntryKind, MergeResult};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::{debug, info};
+
+/// Runs `script` once per merged cluster, context, and user. Each entry is
+/// written to the script's stdin as JSON (`{"name": ..., "cluster": ...}`,
+/// as in a kubeconfig) and replaced by the JSON the script prints. The entry
+/// kind is passed in `KUBEMERGE_ENTRY_KIND`. Entries cannot be renamed.
+pub fn apply_transform_script(
+    result: &mut MergeResult,
+    script: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Transforming entries with {}", script.display());
+    let config = &mut result.config;
+    transform_entries(
+        &mut config.clusters,
+        EntryKind::Cluster,
+        |c: &NamedCluster| &c.name,
+        script,
+    )?;
+    transform_entries(
+        &mut config.contexts,
+        EntryKind::Context,
+        |c: &NamedContext| &c.name,
+        script,
+    )?;
+    transform_entries(
+        &mut config.users,
+        EntryKind::User,
+        |u: &NamedUser| &u.name,
+        script,
+    )
+}
+
+fn transform_entries<T: Serialize + DeserializeOwned>(
+    entries: &mut Option<Vec<T>>,
+    kind: EntryKind,
+    name: fn(&T) -> &String,
+    script: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in entries.iter_mut().flatten() {
+        let entry_name = name(entry).clone();
+        let input = serde_json::to_vec(entry)?;
+        let output = run_script(script, kind, &input).map_err(|e| {
+            format!(
+                "Transform script failed on {} '{}': {}",
+                kind, entry_name, e
+            )
+        })?;
+        let transformed: T = serde_json::from_slice(&output).map_err(|e| {
+            format!(
+                "Transform script returned invalid JSON for {} '{}': {}",
+                kind, entry_name, e
+            )
+        })?;
+        if name(&transformed) != &entry_name {
+            return Err(format!(
+                "Transform script renamed {} '{}' to '{}', which is not supported",
+                kind,
+                entry_name,
+                name(&transformed)
+            )
+            .into());
+        }
+        debug!("Transformed {} '{}'", kind, entry_name);
+        *entry = transformed;
+    }
+    Ok(())
+}
+
+fn run_script(script: &Path, kind: EntryKind, input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(script)
+        .env("KUBEMERGE_ENTRY_KIND", kind.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", script.display(), e))?;
+
+    let mut stdin = child.stdin.take();
+    let (written, output) = thread::scope(|scope| {
+        let writer = scope.spawn(move || match &mut stdin {
+            Some(stdin) => stdin.write_all(input),
+            None => Ok(()),
+        });
+        let output = child.wait_with_output();
+        (writer.join(), output)
+    });
+    let output = output.map_err(|e| format!("cannot read the script output: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    written
+        .map_err(|_| "the thread writing to the script panicked".to_string())?
+        .map_err(|e| format!("cannot write to the script: {}", e))?;
+    Ok(output.stdout)
+}
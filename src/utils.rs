@@ -1,49 +1,701 @@
 use crate::config::KubeConfig;
-use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveDateTime, SecondsFormat};
+use std::collections::HashSet;
+use std::env;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use tracing::{debug, info};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
-pub fn create_backup(output_file: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+/// Timestamp format of the suffix appended by [`create_backup`].
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+pub fn create_backup(output_file: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let timestamp = Local::now().format(BACKUP_TIMESTAMP_FORMAT);
     let backup_name = format!("{}.backup.{}", output_file, timestamp);
     fs::copy(output_file, &backup_name)?;
     info!("Created backup: {}", backup_name);
+    Ok(backup_name)
+}
+
+/// A backup made by [`create_backup`], with the time taken from its name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Backup {
+    pub created: NaiveDateTime,
+    pub path: PathBuf,
+}
+
+/// Lists the backups [`create_backup`] made of `output_file`, oldest first.
+pub fn list_backups(output_file: &str) -> Result<Vec<Backup>, Box<dyn std::error::Error>> {
+    let output_path = Path::new(output_file);
+    let prefix = match output_path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{}.backup.", name),
+        None => return Ok(Vec::new()),
+    };
+    let dir = match output_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let timestamp = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|suffix| NaiveDateTime::parse_from_str(suffix, BACKUP_TIMESTAMP_FORMAT).ok());
+        if let Some(created) = timestamp
+            && path.is_file()
+        {
+            backups.push(Backup { created, path });
+        }
+    }
+
+    backups.sort();
+    Ok(backups)
+}
+
+/// Formats an age the way people say it: "just now", "5 minutes ago".
+pub fn format_age(age: chrono::TimeDelta) -> String {
+    let (count, unit) = match age.num_seconds() {
+        ..60 => return "just now".to_string(),
+        s @ ..3600 => (s / 60, "minute"),
+        s @ ..86_400 => (s / 3600, "hour"),
+        s => (s / 86_400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// Formats a byte count with a binary unit, e.g. "1.5 KiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Warns when an existing output file grants group or other permissions,
+/// since the credentials it held may already have been exposed.
+#[cfg(unix)]
+pub fn warn_if_exposed(output_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(output_file)?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        warn!(
+            "Existing {} has mode {:o}, which allows access beyond its owner; its credentials may have been exposed",
+            output_file, mode
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn warn_if_exposed(_output_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
+
+/// Parses an `fd:N` output target into its file descriptor number.
+/// Returns `Ok(None)` for ordinary output paths.
+pub fn parse_output_fd(output_file: &str) -> Result<Option<i32>, String> {
+    let Some(fd) = output_file.strip_prefix("fd:") else {
+        return Ok(None);
+    };
+    fd.parse::<i32>()
+        .ok()
+        .filter(|fd| *fd >= 0)
+        .map(Some)
+        .ok_or_else(|| format!("Invalid file descriptor in output '{}'", output_file))
+}
+
+/// Writes `content` to an inherited file descriptor and closes it, so a
+/// reader on the other end of a pipe or socket sees end of file.
+#[cfg(unix)]
+pub fn write_to_fd(fd: i32, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    // Taking ownership of a descriptor that is not open is undefined
+    // behaviour, so make sure the process actually holds it first.
+    if fs::symlink_metadata(format!("/dev/fd/{}", fd)).is_err() {
+        return Err(format!("File descriptor {} is not open", fd).into());
+    }
+    // SAFETY: the descriptor is open and was handed to us for this output;
+    // nothing else in the process uses it.
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    file.write_all(content.as_bytes())
+        .map_err(|e| format!("Failed to write to file descriptor {}: {}", fd, e))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write_to_fd(fd: i32, _content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!(
+        "Writing to file descriptor {} is only supported on Unix",
+        fd
+    )
+    .into())
+}
+
+/// Reports whether the existing output already holds `content`, ignoring
+/// the generated comment header so a new timestamp alone does not count as
+/// a change.
+pub fn is_unchanged(output_file: &str, content: &str) -> bool {
+    fs::read_to_string(output_file)
+        .map(|existing| {
+            let (old_header, old_body) = split_header(&existing);
+            let (new_header, new_body) = split_header(content);
+            old_body == new_body && old_header.is_empty() == new_header.is_empty()
+        })
+        .unwrap_or(false)
+}
+
+pub const DEFAULT_HEADER: &str =
+    "Generated by kubemerge at {timestamp} from {files} files — do not edit by hand";
+
+/// Renders a header template into a YAML comment block. `{timestamp}` and
+/// `{files}` are substituted and every line is commented out.
+pub fn render_header(template: &str, files: usize) -> String {
+    let rendered = template
+        .replace(
+            "{timestamp}",
+            &Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        )
+        .replace("{files}", &files.to_string());
+    rendered
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with('#') {
+                format!("{}\n", line)
+            } else if line.is_empty() {
+                "#\n".to_string()
+            } else {
+                format!("# {}\n", line)
+            }
+        })
+        .collect()
+}
+
+/// Splits leading comment lines from the rest of a YAML document.
+fn split_header(content: &str) -> (&str, &str) {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if !line.starts_with('#') {
+            break;
+        }
+        offset += line.len();
+    }
+    content.split_at(offset)
+}
+
+pub fn verify_output(
+    output_file: &str,
+    expected: &KubeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(output_file)
+        .map_err(|e| format!("Failed to re-read {}: {}", output_file, e))?;
+    let written: KubeConfig = serde_yml::from_str(&content)
+        .map_err(|e| format!("Failed to re-parse {}: {}", output_file, e))?;
+
+    let names = |config: &KubeConfig| {
+        (
+            config
+                .clusters
+                .iter()
+                .flatten()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>(),
+            config
+                .contexts
+                .iter()
+                .flatten()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>(),
+            config
+                .users
+                .iter()
+                .flatten()
+                .map(|u| u.name.clone())
+                .collect::<Vec<_>>(),
+            config.current_context.clone(),
+        )
+    };
+
+    if names(&written) != names(expected) {
+        return Err(format!(
+            "Written output {} does not match the merge result",
+            output_file
+        )
+        .into());
+    }
+
+    debug!("Verified output file: {}", output_file);
+    Ok(())
+}
+
+pub fn restore_backup(
+    backup: Option<&str>,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match backup {
+        Some(backup_name) => {
+            fs::copy(backup_name, output_file)?;
+            warn!("Restored {} from backup {}", output_file, backup_name);
+        }
+        None => {
+            fs::remove_file(output_file)?;
+            warn!("Removed unverified output {}", output_file);
+        }
+    }
     Ok(())
 }
 
+/// Reports whether an input is a glob or brace pattern rather than a path.
+/// An existing path is read as it is, even if it contains pattern characters
+/// as in `configs[1]` or `team{a}`.
+pub fn is_input_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '[', '{']) && !Path::new(input).exists()
+}
+
+/// Expands an input pattern such as `~/.kube/{prod,staging}/*.yaml` into
+/// the files it matches: a leading `~` becomes the home directory, braces
+/// expand as in the shell, and each alternative is globbed. Files matched by
+/// more than one alternative are listed once, in order of first match. Like a
+/// directory scan, only files with a kubeconfig extension are kept and
+/// `--exclude` patterns are applied.
+pub fn expand_input_pattern(
+    pattern: &str,
+    options: &ScanOptions,
+) -> Result<ScannedFiles, Box<dyn std::error::Error>> {
+    let pattern = match (pattern.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => pattern.to_string(),
+    };
+
+    let mut seen = HashSet::new();
+    let mut scanned = ScannedFiles::default();
+    for alternative in expand_braces(&pattern) {
+        let paths = glob::glob(&alternative)
+            .map_err(|e| format!("Invalid input pattern {}: {}", alternative, e))?;
+        for path in paths {
+            let path = match path {
+                Ok(path) => path,
+                Err(e) if is_permission_denied(e.error(), options.strict_io) => {
+                    warn!("Permission denied: {}, skipping", e.path().display());
+                    continue;
+                }
+                Err(e) => return Err(format!("Failed to read {}", e).into()),
+            };
+            if !path.is_file()
+                || !is_yaml_file(&path, options)
+                || !seen.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone()))
+            {
+                continue;
+            }
+            if should_exclude(&path, &options.exclude_patterns) {
+                debug!("Excluded file: {}", path.display());
+                scanned.excluded.push(path);
+            } else {
+                scanned.files.push(path);
+            }
+        }
+    }
+    debug!(
+        "Input pattern {} matched {} files",
+        pattern,
+        scanned.files.len()
+    );
+    Ok(scanned)
+}
+
+/// Expands the first `{a,b}` group and recurses on the results, so nested
+/// and repeated groups expand too. Groups without a comma are kept as is.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    let mut depth = 0;
+    let mut bounds = vec![open];
+    let mut close = None;
+    for (i, c) in pattern.char_indices().skip_while(|(i, _)| *i < open) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => bounds.push(i),
+            _ => {}
+        }
+    }
+
+    let Some(close) = close else {
+        return vec![pattern.to_string()];
+    };
+    let suffix = &pattern[close + 1..];
+    if bounds.len() == 1 {
+        return expand_braces(suffix)
+            .into_iter()
+            .map(|rest| format!("{}{}", &pattern[..=close], rest))
+            .collect();
+    }
+
+    bounds.push(close);
+    bounds
+        .windows(2)
+        .flat_map(|pair| {
+            let alternative = &pattern[pair[0] + 1..pair[1]];
+            expand_braces(&format!("{}{}{}", &pattern[..open], alternative, suffix))
+        })
+        .collect()
+}
+
+pub fn expand_output_path(output_file: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if !output_file.contains('%') {
+        return Ok(output_file.to_string());
+    }
+
+    let mut items = Vec::new();
+    for item in StrftimeItems::new(output_file) {
+        if matches!(item, Item::Error) {
+            return Err(format!("Invalid date placeholder in output path: {}", output_file).into());
+        }
+        items.push(item);
+    }
+
+    let expanded = Local::now()
+        .format_with_items(items.into_iter())
+        .to_string();
+    debug!("Expanded output path {} to {}", output_file, expanded);
+    Ok(expanded)
+}
+
+#[derive(Debug)]
+pub struct ScanOptions {
+    pub exclude_patterns: Vec<String>,
+    pub extensions: Vec<String>,
+    pub include_extensionless: bool,
+    /// Abort on unreadable files instead of skipping them.
+    pub strict_io: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            exclude_patterns: Vec::new(),
+            extensions: vec!["yaml".to_string(), "yml".to_string()],
+            include_extensionless: false,
+            strict_io: false,
+        }
+    }
+}
+
+/// Files found by [`find_yaml_files`], along with those dropped by an
+/// exclude pattern.
+#[derive(Debug, Default)]
+pub struct ScannedFiles {
+    pub files: Vec<PathBuf>,
+    pub excluded: Vec<PathBuf>,
+}
+
 pub fn find_yaml_files(
     dir: &str,
-    exclude_patterns: &[&String],
-) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let mut yaml_files = Vec::new();
+    options: &ScanOptions,
+) -> Result<ScannedFiles, Box<dyn std::error::Error>> {
+    let mut scanned = ScannedFiles::default();
 
     debug!("Scanning directory: {}", dir);
     for entry in fs::read_dir(dir)? {
-        let entry = entry?;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if is_permission_denied(&e, options.strict_io) => {
+                warn!("Permission denied: entry in {}, skipping", dir);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
         let path = entry.path();
 
-        if path.is_file() && is_yaml_file(&path) && !should_exclude(&path, exclude_patterns) {
-            debug!("Found YAML file: {}", path.display());
-            yaml_files.push(path);
-        } else if should_exclude(&path, exclude_patterns) {
+        if !path.is_file() || !is_yaml_file(&path, options) {
+            continue;
+        }
+        if should_exclude(&path, &options.exclude_patterns) {
             debug!("Excluded file: {}", path.display());
+            scanned.excluded.push(path);
+        } else {
+            debug!("Found YAML file: {}", path.display());
+            scanned.files.push(path);
         }
     }
 
-    yaml_files.sort();
-    debug!("Found {} YAML files total", yaml_files.len());
-    Ok(yaml_files)
+    scanned.files.sort();
+    scanned.excluded.sort();
+    debug!("Found {} YAML files total", scanned.files.len());
+    Ok(scanned)
 }
 
-fn is_yaml_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| ext == "yaml" || ext == "yml")
-        .unwrap_or(false)
+/// Whether an IO error is a permission error that should skip the file
+/// rather than abort the run, which is the case unless `strict_io` is set.
+pub fn is_permission_denied(error: &io::Error, strict_io: bool) -> bool {
+    !strict_io && error.kind() == io::ErrorKind::PermissionDenied
+}
+
+pub fn read_file_list(source: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let content = if source == "-" {
+        debug!("Reading file list from stdin");
+        io::read_to_string(io::stdin())?
+    } else {
+        debug!("Reading file list from {}", source);
+        fs::read_to_string(source)
+            .map_err(|e| format!("Failed to read file list {}: {}", source, e))?
+    };
+
+    let files: Vec<PathBuf> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect();
+
+    debug!("Read {} paths from file list", files.len());
+    Ok(files)
+}
+
+/// Expands `${VAR}` and `$VAR` references against the current environment;
+/// `$$` produces a literal `$`. Undefined variables are an error unless
+/// `allow_undefined` is set, in which case the reference is left untouched.
+pub fn expand_env_vars(value: &str, allow_undefined: bool) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(escaped) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = escaped;
+            continue;
+        }
+
+        let (name, reference_len) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => return Err(format!("Unterminated variable reference in '{}'", value)),
+            }
+        } else {
+            let end = after
+                .char_indices()
+                .find(|(i, c)| {
+                    !(c.is_ascii_alphanumeric() || *c == '_') || (*i == 0 && c.is_ascii_digit())
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(after.len());
+            (&after[..end], end + 1)
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        match env::var(name) {
+            Ok(var) => expanded.push_str(&var),
+            Err(_) if allow_undefined => expanded.push_str(&rest[pos..pos + reference_len]),
+            Err(_) => {
+                return Err(format!(
+                    "Undefined environment variable '{}' in '{}'",
+                    name, value
+                ));
+            }
+        }
+        rest = &rest[pos + reference_len..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Moves the files named in `priority` to the front, in the listed order.
+/// Entries match either a full path or a bare file name; unlisted files keep
+/// their existing order after the prioritized ones.
+pub fn apply_priority(files: Vec<PathBuf>, priority: &[PathBuf]) -> Vec<PathBuf> {
+    let mut remaining = files;
+    let mut ordered = Vec::with_capacity(remaining.len());
+
+    for entry in priority {
+        let position = remaining
+            .iter()
+            .position(|file| file == entry || file.file_name() == Some(entry.as_os_str()));
+        match position {
+            Some(index) => ordered.push(remaining.remove(index)),
+            None => warn!(
+                "Priority entry {} does not match any input file",
+                entry.display()
+            ),
+        }
+    }
+
+    ordered.extend(remaining);
+    ordered
+}
+
+/// Parses durations such as `500ms`, `5s` or `2m`; bare numbers are seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => return Err(format!("invalid duration unit in '{}'", value)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration '{}': {}", value, e))
+}
+
+/// Reads a file on a worker thread, giving up after `timeout`. Returns
+/// `Ok(None)` on timeout; the stalled read is left to finish in the
+/// background.
+pub fn read_with_timeout(path: &Path, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+    let (sender, receiver) = mpsc::channel();
+    let owned_path = path.to_path_buf();
+    thread::spawn(move || {
+        let _ = sender.send(fs::read(owned_path));
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result.map(Some),
+        Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(io::Error::other("reader thread exited unexpectedly"))
+        }
+    }
+}
+
+/// Runs `command` through the platform shell with `KUBEMERGE_OUTPUT` set to
+/// the output path. A failing hook is an error only when `required`.
+pub fn run_post_hook(
+    command: &str,
+    output_file: &str,
+    required: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    info!("Running post-hook: {}", command);
+    let status = shell
+        .arg(command)
+        .env("KUBEMERGE_OUTPUT", output_file)
+        .status()
+        .map_err(|e| format!("Failed to run post-hook: {}", e))?;
+
+    if status.success() {
+        return Ok(());
+    }
+    if required {
+        return Err(format!("Post-hook failed with {}", status).into());
+    }
+    warn!("Post-hook failed with {}", status);
+    Ok(())
+}
+
+/// Compares paths by their canonical form, falling back to a plain
+/// comparison when either cannot be resolved.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Extracts the host from a server URL such as `https://user@host:6443/path`.
+/// IPv6 hosts are returned without brackets.
+pub fn server_host(server: &str) -> Option<&str> {
+    server_address(server).map(|(host, _)| host)
+}
+
+/// Extracts host and port from a server URL, defaulting the port from the
+/// scheme (443 unless the scheme is `http`).
+pub fn server_address(server: &str) -> Option<(&str, u16)> {
+    let (scheme, rest) = server.split_once("://").unwrap_or(("https", server));
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, hp)| hp);
+
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, after) = bracketed.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if host.is_empty() {
+        return None;
+    }
+
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None if scheme.eq_ignore_ascii_case("http") => 80,
+        None => 443,
+    };
+    Some((host, port))
+}
+
+pub fn is_yaml_file(path: &Path, options: &ScanOptions) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => options
+            .extensions
+            .iter()
+            .any(|allowed| allowed.trim_start_matches('.') == ext),
+        None => options.include_extensionless,
+    }
 }
 
-fn should_exclude(path: &Path, exclude_patterns: &[&String]) -> bool {
+/// Whether the file name of `path` contains any of the `--exclude` patterns.
+pub fn should_exclude(path: &Path, exclude_patterns: &[String]) -> bool {
     let filename = path
         .file_name()
         .and_then(|name| name.to_str())
@@ -0,0 +1,465 @@
+use crate::certs::{certificate_expiry, load_certificate};
+use crate::config::{KubeConfig, User};
+use crate::merge::{EntryKind, Sources};
+use crate::style;
+use chrono::{Duration, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    MissingCurrentContext,
+    DanglingClusterRef,
+    DanglingUserRef,
+    MissingAuth,
+    ConflictingAuth,
+    ExpiredCert,
+    ExpiringCert,
+    InvalidCert,
+    InsecureCluster,
+    OrphanedCluster,
+    OrphanedUser,
+    DuplicateCredentials,
+    UnreachableCluster,
+    InvalidName,
+    SchemaViolation,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            IssueKind::MissingCurrentContext => "missing current context",
+            IssueKind::DanglingClusterRef => "dangling cluster references",
+            IssueKind::DanglingUserRef => "dangling user references",
+            IssueKind::MissingAuth => "missing authentication",
+            IssueKind::ConflictingAuth => "conflicting authentication",
+            IssueKind::ExpiredCert => "expired certificates",
+            IssueKind::ExpiringCert => "expiring certificates",
+            IssueKind::InvalidCert => "unreadable certificates",
+            IssueKind::InsecureCluster => "insecure clusters",
+            IssueKind::OrphanedCluster => "orphaned clusters",
+            IssueKind::OrphanedUser => "orphaned users",
+            IssueKind::DuplicateCredentials => "duplicate credentials",
+            IssueKind::UnreachableCluster => "unreachable clusters",
+            IssueKind::InvalidName => "invalid names",
+            IssueKind::SchemaViolation => "schema violations",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub kind: IssueKind,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{} ({})", self.message, source.display()),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// RFC 1123 subdomain: lowercase alphanumerics and '-', in '.'-separated
+/// labels that start and end with an alphanumeric.
+pub const DEFAULT_NAME_PATTERN: &str =
+    r"[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*";
+
+#[derive(Debug, Default)]
+pub struct ValidateOptions {
+    pub skip_reference_check: bool,
+    /// Names of clusters, contexts, and users must match this in full.
+    pub name_pattern: Option<Regex>,
+    /// An empty current-context is an error rather than acceptable.
+    pub require_current_context: bool,
+}
+
+pub fn validate_config(
+    config: &KubeConfig,
+    sources: &Sources,
+    options: &ValidateOptions,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let source_of = |kind: EntryKind, name: &str| sources.get(&(kind, name.to_string())).cloned();
+
+    if options.require_current_context && config.current_context.is_empty() {
+        issues.push(ValidationIssue {
+            severity: Severity::Error,
+            kind: IssueKind::MissingCurrentContext,
+            name: String::new(),
+            source: None,
+            message: "No current-context set in the merged config".to_string(),
+        });
+    }
+
+    if !config.current_context.is_empty()
+        && let Some(contexts) = &config.contexts
+        && !contexts.iter().any(|c| c.name == config.current_context)
+    {
+        issues.push(ValidationIssue {
+            severity: Severity::Error,
+            kind: IssueKind::MissingCurrentContext,
+            name: config.current_context.clone(),
+            source: None,
+            message: format!(
+                "Current context '{}' not found in merged contexts",
+                config.current_context
+            ),
+        });
+    }
+
+    if !options.skip_reference_check
+        && let Some(contexts) = &config.contexts
+    {
+        let cluster_names: Vec<&String> = config
+            .clusters
+            .as_ref()
+            .map(|c| c.iter().map(|cluster| &cluster.name).collect())
+            .unwrap_or_default();
+        let user_names: Vec<&String> = config
+            .users
+            .as_ref()
+            .map(|u| u.iter().map(|user| &user.name).collect())
+            .unwrap_or_default();
+
+        for context in contexts {
+            if !cluster_names.contains(&&context.context.cluster) {
+                issues.push(ValidationIssue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::DanglingClusterRef,
+                    name: context.name.clone(),
+                    source: source_of(EntryKind::Context, &context.name),
+                    message: format!(
+                        "Context '{}' references missing cluster '{}'",
+                        context.name, context.context.cluster
+                    ),
+                });
+            }
+            if !user_names.contains(&&context.context.user) {
+                issues.push(ValidationIssue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::DanglingUserRef,
+                    name: context.name.clone(),
+                    source: source_of(EntryKind::Context, &context.name),
+                    message: format!(
+                        "Context '{}' references missing user '{}'",
+                        context.name, context.context.user
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some(pattern) = &options.name_pattern {
+        let names = config
+            .clusters
+            .iter()
+            .flatten()
+            .map(|c| (EntryKind::Cluster, &c.name))
+            .chain(
+                config
+                    .contexts
+                    .iter()
+                    .flatten()
+                    .map(|c| (EntryKind::Context, &c.name)),
+            )
+            .chain(
+                config
+                    .users
+                    .iter()
+                    .flatten()
+                    .map(|u| (EntryKind::User, &u.name)),
+            );
+        for (kind, name) in names {
+            if !pattern.is_match(name) {
+                issues.push(ValidationIssue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::InvalidName,
+                    name: name.clone(),
+                    source: source_of(kind, name),
+                    message: format!(
+                        "Name of {} '{}' does not match the name pattern",
+                        kind, name
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some(users) = &config.users {
+        for user in users {
+            if !has_auth(&user.user) {
+                issues.push(ValidationIssue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::MissingAuth,
+                    name: user.name.clone(),
+                    source: source_of(EntryKind::User, &user.name),
+                    message: format!("User '{}' has no authentication method", user.name),
+                });
+            }
+            let methods = auth_methods(&user.user);
+            if methods.len() > 1 {
+                issues.push(ValidationIssue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::ConflictingAuth,
+                    name: user.name.clone(),
+                    source: source_of(EntryKind::User, &user.name),
+                    message: format!(
+                        "User '{}' has several authentication methods ({}), which usually means a source or merge error",
+                        user.name,
+                        methods.join(", ")
+                    ),
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+/// Certificates expiring within this many days are reported as expiring.
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// Health checks run by `doctor` on top of [`validate_config`]: certificate
+/// expiry, insecure clusters, orphaned entries, and shared credentials.
+pub fn doctor_checks(config: &KubeConfig, sources: &Sources) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let source_of = |kind: EntryKind, name: &str| sources.get(&(kind, name.to_string())).cloned();
+    let issue = |severity, kind, entry: EntryKind, name: &str, message: String| ValidationIssue {
+        severity,
+        kind,
+        name: name.to_string(),
+        source: source_of(entry, name),
+        message,
+    };
+    let contexts: Vec<_> = config.contexts.iter().flatten().collect();
+
+    for cluster in config.clusters.iter().flatten() {
+        if cluster.cluster.insecure_skip_tls_verify == Some(true) {
+            issues.push(issue(
+                Severity::Warning,
+                IssueKind::InsecureCluster,
+                EntryKind::Cluster,
+                &cluster.name,
+                format!("Cluster '{}' skips TLS verification", cluster.name),
+            ));
+        }
+        if !contexts.iter().any(|c| c.context.cluster == cluster.name) {
+            issues.push(issue(
+                Severity::Warning,
+                IssueKind::OrphanedCluster,
+                EntryKind::Cluster,
+                &cluster.name,
+                format!("Cluster '{}' is not used by any context", cluster.name),
+            ));
+        }
+        let certificate = load_certificate(
+            cluster.cluster.certificate_authority_data.as_deref(),
+            cluster.cluster.certificate_authority.as_deref(),
+        );
+        if let Some(found) = check_certificate(certificate, "Cluster", &cluster.name) {
+            let (severity, kind, message) = found;
+            issues.push(issue(
+                severity,
+                kind,
+                EntryKind::Cluster,
+                &cluster.name,
+                message,
+            ));
+        }
+    }
+
+    let mut seen_credentials: Vec<(&str, &str)> = Vec::new();
+    for user in config.users.iter().flatten() {
+        if !contexts.iter().any(|c| c.context.user == user.name) {
+            issues.push(issue(
+                Severity::Warning,
+                IssueKind::OrphanedUser,
+                EntryKind::User,
+                &user.name,
+                format!("User '{}' is not used by any context", user.name),
+            ));
+        }
+
+        let credential = user
+            .user
+            .token
+            .as_deref()
+            .or(user.user.client_certificate_data.as_deref());
+        if let Some(credential) = credential {
+            match seen_credentials.iter().find(|(c, _)| *c == credential) {
+                Some((_, first)) => issues.push(issue(
+                    Severity::Warning,
+                    IssueKind::DuplicateCredentials,
+                    EntryKind::User,
+                    &user.name,
+                    format!(
+                        "User '{}' shares its credentials with user '{}'",
+                        user.name, first
+                    ),
+                )),
+                None => seen_credentials.push((credential, &user.name)),
+            }
+        }
+
+        let certificate = load_certificate(
+            user.user.client_certificate_data.as_deref(),
+            user.user.client_certificate.as_deref(),
+        );
+        if let Some(found) = check_certificate(certificate, "User", &user.name) {
+            let (severity, kind, message) = found;
+            issues.push(issue(severity, kind, EntryKind::User, &user.name, message));
+        }
+    }
+
+    issues
+}
+
+fn check_certificate(
+    certificate: Result<Option<Vec<u8>>, String>,
+    owner: &str,
+    name: &str,
+) -> Option<(Severity, IssueKind, String)> {
+    let expiry =
+        match certificate.and_then(|bytes| bytes.map(|b| certificate_expiry(&b)).transpose()) {
+            Ok(Some(expiry)) => expiry,
+            Ok(None) => return None,
+            Err(e) => {
+                return Some((
+                    Severity::Warning,
+                    IssueKind::InvalidCert,
+                    format!("{} '{}' has an unreadable certificate: {}", owner, name, e),
+                ));
+            }
+        };
+
+    let now = Utc::now();
+    if expiry <= now {
+        Some((
+            Severity::Error,
+            IssueKind::ExpiredCert,
+            format!(
+                "{} '{}' certificate expired on {}",
+                owner,
+                name,
+                expiry.format("%Y-%m-%d")
+            ),
+        ))
+    } else if expiry - now < Duration::days(EXPIRY_WARNING_DAYS) {
+        Some((
+            Severity::Warning,
+            IssueKind::ExpiringCert,
+            format!(
+                "{} '{}' certificate expires on {}",
+                owner,
+                name,
+                expiry.format("%Y-%m-%d")
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Names the mutually exclusive authentication methods a user configures.
+pub fn auth_methods(user: &User) -> Vec<&'static str> {
+    let mut methods = Vec::new();
+    if user.token.is_some() || user.token_file.is_some() {
+        methods.push("token");
+    }
+    if user.client_certificate_data.is_some() || user.client_certificate.is_some() {
+        methods.push("client certificate");
+    }
+    if user.username.is_some() || user.password.is_some() {
+        methods.push("basic auth");
+    }
+    if user.exec.is_some() {
+        methods.push("exec");
+    }
+    if user.auth_provider.is_some() {
+        methods.push("auth-provider");
+    }
+    methods
+}
+
+fn has_auth(user: &User) -> bool {
+    !auth_methods(user).is_empty()
+}
+
+pub fn has_errors(issues: &[ValidationIssue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+pub fn log_issues(issues: &[ValidationIssue]) {
+    for issue in issues {
+        match issue.severity {
+            Severity::Error => error!("{}", style::error(issue)),
+            Severity::Warning => warn!("{}", style::warning(issue)),
+        }
+    }
+}
+
+/// Logs issues grouped by kind, in the order each kind was first found.
+pub fn print_categorized(issues: &[ValidationIssue]) {
+    let mut kinds: Vec<IssueKind> = Vec::new();
+    for issue in issues {
+        if !kinds.contains(&issue.kind) {
+            kinds.push(issue.kind);
+        }
+    }
+
+    for kind in kinds {
+        let group: Vec<_> = issues.iter().filter(|i| i.kind == kind).collect();
+        info!("{} ({}):", kind, group.len());
+        for issue in group {
+            match issue.severity {
+                Severity::Error => error!("  - {}", style::error(issue)),
+                Severity::Warning => warn!("  - {}", style::warning(issue)),
+            }
+        }
+    }
+
+    if issues.is_empty() {
+        info!("No problems found");
+    }
+}
+
+pub fn print_report(
+    issues: &[ValidationIssue],
+    format: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(issues)?),
+        _ => {
+            log_issues(issues);
+            let errors = issues
+                .iter()
+                .filter(|i| i.severity == Severity::Error)
+                .count();
+            info!(
+                "Validation found {} errors and {} warnings",
+                errors,
+                issues.len() - errors
+            );
+        }
+    }
+    Ok(())
+}
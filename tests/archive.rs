@@ -0,0 +1,57 @@
+use kubemerge::archive::read_archive;
+use kubemerge::utils::ScanOptions;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+const CONFIG: &str = "apiVersion: v1\nkind: Config\n";
+
+fn write_archive(name: &str, members: &[&str]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("kubemerge-{}-{}.zip", name, std::process::id()));
+    let mut zip = ZipWriter::new(File::create(&path).unwrap());
+    for member in members {
+        zip.start_file(*member, SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(CONFIG.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    path
+}
+
+#[test]
+fn exclude_patterns_apply_to_members() {
+    let path = write_archive(
+        "exclude",
+        &["prod.yaml", "staging.yaml", "old/prod.backup.yaml"],
+    );
+    let options = ScanOptions {
+        exclude_patterns: vec!["backup".to_string(), "staging".to_string()],
+        ..ScanOptions::default()
+    };
+    let contents = read_archive(&path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let members: Vec<&Path> = contents.members.iter().map(|(p, _)| p.as_path()).collect();
+    assert_eq!(members, [path.join("prod.yaml")]);
+    assert_eq!(
+        contents.excluded,
+        [path.join("staging.yaml"), path.join("old/prod.backup.yaml")]
+    );
+}
+
+#[test]
+fn members_at_the_same_path_are_an_error() {
+    let path = write_archive("duplicate", &["prod.yaml", "./prod.yaml"]);
+    let error = read_archive(&path, &ScanOptions::default()).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(
+        error
+            .to_string()
+            .contains("more than one member at ./prod.yaml"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::merge;
+use kubemerge::merge::{MergeOptions, parse_kubeconfig};
+use std::path::Path;
+
+const BOM_CRLF: &[u8] = include_bytes!("fixtures/bom-crlf.yaml");
+
+#[test]
+fn bom_prefixed_crlf_config_merges_like_the_clean_one() {
+    let clean = String::from_utf8(BOM_CRLF[3..].to_vec())
+        .unwrap()
+        .replace("\r\n", "\n");
+    assert!(BOM_CRLF.starts_with("\u{feff}".as_bytes()));
+    assert!(!clean.starts_with('\u{feff}'));
+
+    let options = MergeOptions::default();
+    let from_bom = parse_kubeconfig(Path::new("bom.yaml"), BOM_CRLF.to_vec(), &options)
+        .unwrap()
+        .unwrap();
+    let from_clean = parse_kubeconfig(Path::new("clean.yaml"), clean.into_bytes(), &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(from_bom, from_clean);
+    assert_eq!(from_bom.api_version, "v1");
+
+    let merged_bom = serde_yml::to_string(&merge(vec![from_bom]).config).unwrap();
+    let merged_clean = serde_yml::to_string(&merge(vec![from_clean]).config).unwrap();
+    assert_eq!(merged_bom, merged_clean);
+}
+
+#[test]
+fn bom_only_file_is_empty() {
+    let parsed = parse_kubeconfig(
+        Path::new("empty.yaml"),
+        "\u{feff}\r\n".as_bytes().to_vec(),
+        &MergeOptions::default(),
+    )
+    .unwrap();
+    assert!(parsed.is_none());
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::merge;
+use kubemerge::config::KubeConfig;
+use serde_yml::Value;
+
+const ENTERPRISE_USER: &str = include_str!("fixtures/enterprise-user.yaml");
+const SNI_CLUSTERS: &str = include_str!("fixtures/sni-clusters.yaml");
+
+fn canonical(config: &KubeConfig) -> String {
+    let mut config = config.clone();
+    config.sort_entries();
+    config.to_canonical_yaml().unwrap()
+}
+
+#[test]
+fn canonical_output_is_stable_across_round_trips() {
+    let inputs: Vec<KubeConfig> = [ENTERPRISE_USER, SNI_CLUSTERS]
+        .iter()
+        .map(|yaml| serde_yml::from_str(yaml).unwrap())
+        .collect();
+
+    let first = canonical(&merge(inputs.clone()).config);
+    let reparsed: KubeConfig = serde_yml::from_str(&first).unwrap();
+    assert_eq!(canonical(&reparsed), first);
+    assert_eq!(canonical(&merge(vec![reparsed]).config), first);
+
+    let reversed = canonical(&merge(inputs.into_iter().rev().collect()).config);
+    let without_current = |output: &str| {
+        output
+            .lines()
+            .filter(|line| !line.starts_with("current-context:"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(without_current(&reversed), without_current(&first));
+}
+
+fn assert_keys_sorted(value: &Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            let keys: Vec<&str> = mapping.keys().filter_map(Value::as_str).collect();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+            mapping.values().for_each(assert_keys_sorted);
+        }
+        Value::Sequence(items) => items.iter().for_each(assert_keys_sorted),
+        _ => {}
+    }
+}
+
+#[test]
+fn canonical_output_sorts_every_mapping() {
+    let config: KubeConfig = serde_yml::from_str(ENTERPRISE_USER).unwrap();
+    let output: Value = serde_yml::from_str(&canonical(&config)).unwrap();
+    assert_keys_sorted(&output);
+}
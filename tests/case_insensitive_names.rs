@@ -0,0 +1,59 @@
+mod common;
+
+use common::{assert_skipped, merge_with};
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::{MergeOptions, SkipReason};
+use kubemerge::validate::{ValidateOptions, validate_config};
+
+const FIRST: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: prod
+  cluster: {server: "https://prod:6443"}
+contexts:
+- name: prod
+  context: {cluster: prod, user: prod}
+users:
+- name: prod
+  user: {token: first}
+"#;
+
+const SECOND: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: Prod
+  cluster: {server: "https://prod:6443"}
+contexts:
+- name: Prod-Admin
+  context: {cluster: Prod, user: Prod, namespace: kube-system}
+users:
+- name: Prod
+  user: {token: second}
+current-context: Prod-Admin
+"#;
+
+#[test]
+fn references_follow_the_kept_casing() {
+    let configs = [FIRST, SECOND]
+        .iter()
+        .map(|yaml| serde_yml::from_str::<KubeConfig>(yaml).unwrap())
+        .collect();
+    let options = MergeOptions {
+        case_insensitive_names: true,
+        ..MergeOptions::default()
+    };
+    let result = merge_with(configs, &options);
+    assert_skipped(&result, "Prod", SkipReason::DuplicateIdentical);
+    assert_skipped(&result, "Prod", SkipReason::DuplicateConflicting);
+
+    let admin = &result.config.contexts.as_ref().unwrap()[1];
+    assert_eq!(admin.name, "Prod-Admin");
+    assert_eq!(admin.context.cluster, "prod");
+    assert_eq!(admin.context.user, "prod");
+    assert_eq!(result.config.current_context, "Prod-Admin");
+
+    let issues = validate_config(&result.config, &result.sources, &ValidateOptions::default());
+    assert!(issues.is_empty(), "{:?}", issues);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{assert_skipped, merge};
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::SkipReason;
+use kubemerge::rename::coalesce_clusters;
+
+const CLUSTERS: &str = "
+apiVersion: v1
+kind: Config
+clusters:
+- name: direct
+  cluster:
+    server: https://k8s.example.com
+- name: direct-copy
+  cluster:
+    server: https://k8s.example.com
+- name: proxied
+  cluster:
+    server: https://k8s.example.com
+    proxy-url: http://proxy.example.com:3128
+- name: insecure
+  cluster:
+    server: https://k8s.example.com
+    insecure-skip-tls-verify: true
+contexts:
+- name: copy
+  context:
+    cluster: direct-copy
+    user: admin
+users:
+- name: admin
+  user:
+    token: t
+";
+
+#[test]
+fn only_clusters_connecting_the_same_way_are_coalesced() {
+    let config: KubeConfig = serde_yml::from_str(CLUSTERS).unwrap();
+    let mut result = merge(vec![config]);
+    coalesce_clusters(&mut result);
+
+    let names: Vec<&str> = result
+        .config
+        .clusters
+        .iter()
+        .flatten()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, ["direct", "proxied", "insecure"]);
+    assert_eq!(
+        result.config.contexts.as_ref().unwrap()[0].context.cluster,
+        "direct"
+    );
+    assert_skipped(&result, "direct", SkipReason::Coalesced);
+}
@@ -0,0 +1,49 @@
+// Each test binary uses only some of these helpers.
+#![allow(dead_code)]
+
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::{MergeOptions, MergeResult, SkipReason, merge_configs};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Merges the configs in order, as if read from `0.yaml`, `1.yaml`, ...
+pub fn merge(configs: Vec<KubeConfig>) -> MergeResult {
+    merge_with(configs, &MergeOptions::default())
+}
+
+pub fn merge_with(configs: Vec<KubeConfig>, options: &MergeOptions) -> MergeResult {
+    let sources = configs
+        .into_iter()
+        .enumerate()
+        .map(|(i, config)| (PathBuf::from(format!("{}.yaml", i)), config));
+    merge_configs(sources, options).unwrap()
+}
+
+pub fn assert_skipped(result: &MergeResult, name: &str, reason: SkipReason) {
+    assert!(
+        result
+            .skipped
+            .iter()
+            .any(|entry| entry.name == name && entry.reason == reason),
+        "expected '{}' to be skipped as {:?}, skipped: {:?}",
+        name,
+        reason,
+        result.skipped
+    );
+}
+
+/// A fresh, empty directory under the system temp dir.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kubemerge-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the kubemerge binary with `args`.
+pub fn kubemerge(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_kubemerge"))
+        .args(args)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::merge;
+use kubemerge::config::KubeConfig;
+use kubemerge::rename::template_renames;
+
+const CONTEXTS: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: a{user}
+  cluster: {server: "https://a:6443"}
+contexts:
+- name: braces
+  context: {cluster: "a{user}", user: admin}
+"#;
+
+const KEPT: &str = r#"
+apiVersion: v1
+kind: Config
+contexts:
+- name: first
+  context: {cluster: prod, user: admin, namespace: shared}
+- name: shared
+  context: {cluster: prod, user: admin}
+"#;
+
+#[test]
+fn placeholders_are_filled_in_one_pass() {
+    let config: KubeConfig = serde_yml::from_str(CONTEXTS).unwrap();
+    let result = merge(vec![config]);
+    let renames = template_renames(&result, "{cluster}-{user}", "");
+    assert_eq!(renames[0], ("braces".into(), "a{user}-admin".into()));
+}
+
+#[test]
+fn kept_names_are_deduplicated_too() {
+    let config: KubeConfig = serde_yml::from_str(KEPT).unwrap();
+    let result = merge(vec![config]);
+    let renames = template_renames(&result, "{namespace}", "");
+    assert_eq!(
+        renames,
+        [
+            ("first".to_string(), "shared".to_string()),
+            ("shared".to_string(), "shared-2".to_string()),
+        ]
+    );
+}
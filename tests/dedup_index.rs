@@ -0,0 +1,90 @@
+use kubemerge::config::{
+    Cluster, Context, KubeConfig, NamedCluster, NamedContext, NamedUser, User,
+};
+use kubemerge::merge::{MergeOptions, merge_configs};
+use std::path::PathBuf;
+
+/// Builds file `file` of a synthetic set where every file shares some names
+/// with earlier ones, with content that differs per file.
+fn generate_config(file: usize) -> KubeConfig {
+    let names: Vec<String> = (file * 7..file * 7 + 20)
+        .map(|i| format!("env-{}", i % 60))
+        .collect();
+
+    let mut config = KubeConfig::new();
+    config.clusters = Some(
+        names
+            .iter()
+            .map(|name| NamedCluster {
+                name: name.clone(),
+                cluster: Cluster::new(format!("https://{}-{}.example.com", name, file)),
+            })
+            .collect(),
+    );
+    config.contexts = Some(
+        names
+            .iter()
+            .map(|name| {
+                let mut context = Context::new(name.clone(), name.clone());
+                context.namespace = Some(format!("ns-{}", file));
+                NamedContext {
+                    name: name.clone(),
+                    context,
+                }
+            })
+            .collect(),
+    );
+    config.users = Some(
+        names
+            .iter()
+            .map(|name| {
+                let mut user = User::default();
+                user.token = Some(format!("token-{}-{}", name, file % 3));
+                NamedUser {
+                    name: name.clone(),
+                    user,
+                }
+            })
+            .collect(),
+    );
+    config
+}
+
+/// The straightforward first-wins merge: a linear scan of everything kept
+/// so far for every incoming entry.
+fn linear_merge<T: Clone>(lists: impl Iterator<Item = Vec<T>>, name: fn(&T) -> &str) -> Vec<T> {
+    let mut kept: Vec<T> = Vec::new();
+    for entry in lists.flatten() {
+        if !kept.iter().any(|existing| name(existing) == name(&entry)) {
+            kept.push(entry);
+        }
+    }
+    kept
+}
+
+#[test]
+fn indexed_merge_matches_linear_merge() {
+    let configs: Vec<KubeConfig> = (0..30).map(generate_config).collect();
+    let result = merge_configs(
+        configs
+            .iter()
+            .enumerate()
+            .map(|(i, config)| (PathBuf::from(format!("{}.yaml", i)), config.clone())),
+        &MergeOptions::default(),
+    )
+    .unwrap();
+
+    let clusters = linear_merge(configs.iter().map(|c| c.clusters.clone().unwrap()), |c| {
+        &c.name
+    });
+    let contexts = linear_merge(configs.iter().map(|c| c.contexts.clone().unwrap()), |c| {
+        &c.name
+    });
+    let users = linear_merge(configs.iter().map(|c| c.users.clone().unwrap()), |u| {
+        &u.name
+    });
+
+    assert_eq!(result.config.clusters.unwrap(), clusters);
+    assert_eq!(result.config.contexts.unwrap(), contexts);
+    assert_eq!(result.config.users.unwrap(), users);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{kubemerge, temp_dir};
+use std::fs;
+
+const ENVIRONMENTS: &str = include_str!("fixtures/environments.yaml");
+
+/// Runs kubemerge on the environments fixture with `args`, where `DIR`
+/// stands for an empty split directory. Returns the files left in it and
+/// the captured stderr.
+fn run_split(name: &str, args: &[&str]) -> (Vec<String>, String) {
+    let dir = temp_dir(name);
+    let input = dir.join("in");
+    let split = dir.join("split");
+    fs::create_dir_all(&input).unwrap();
+    fs::create_dir_all(&split).unwrap();
+    fs::write(input.join("environments.yaml"), ENVIRONMENTS).unwrap();
+
+    let split_arg = split.to_str().unwrap().to_string();
+    let output_arg = dir.join("config").to_str().unwrap().to_string();
+    let mut all = vec!["-i", input.to_str().unwrap(), "-o", output_arg.as_str()];
+    all.extend(args.iter().map(|arg| match *arg {
+        "DIR" => split_arg.as_str(),
+        arg => arg,
+    }));
+    let output = kubemerge(&all);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "{}", stderr);
+
+    let files = fs::read_dir(&split)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    (files, stderr)
+}
+
+#[test]
+fn dry_run_split_output_writes_nothing() {
+    let (files, stderr) = run_split(
+        "dry-run-split-output",
+        &["--dry-run", "--split-output", "DIR"],
+    );
+    assert!(files.is_empty(), "wrote {:?}", files);
+    assert!(stderr.contains("Dry run: would write"), "{}", stderr);
+    assert!(stderr.contains("clusters.yaml"), "{}", stderr);
+}
+
+#[test]
+fn dry_run_split_subcommand_writes_nothing() {
+    let (files, stderr) = run_split("dry-run-split", &["--dry-run", "split", "--dir", "DIR"]);
+    assert!(files.is_empty(), "wrote {:?}", files);
+    assert!(stderr.contains("kind-local.yaml"), "{}", stderr);
+}
+
+#[test]
+fn diff_split_output_writes_nothing() {
+    let (files, _) = run_split(
+        "diff-split-output",
+        &["--diff=unified", "--split-output", "DIR"],
+    );
+    assert!(files.is_empty(), "wrote {:?}", files);
+}
+
+#[test]
+fn split_output_writes_every_section() {
+    let (mut files, _) = run_split("split-output", &["--split-output", "DIR"]);
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            "clusters.yaml",
+            "contexts.yaml",
+            "current-context.yaml",
+            "users.yaml"
+        ]
+    );
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{kubemerge, temp_dir};
+use std::fs;
+
+const RECIPIENT: &str = "age:age1mrl6uqc9d77lztje58vgm8he4x7a40wa0xzzdmdmrwh4jwc44ukq98w7fj";
+const ENVIRONMENTS: &str = include_str!("fixtures/environments.yaml");
+
+fn assert_refused(name: &str, args: &[&str]) {
+    let dir = temp_dir(name);
+    let input = dir.join("in");
+    let out = dir.join("out");
+    fs::create_dir_all(&input).unwrap();
+    fs::write(input.join("environments.yaml"), ENVIRONMENTS).unwrap();
+
+    let out_arg = out.to_str().unwrap().to_string();
+    let args: Vec<&str> = args
+        .iter()
+        .map(|arg| if *arg == "OUT" { out_arg.as_str() } else { arg })
+        .collect();
+    let mut all = vec!["-i", input.to_str().unwrap(), "--encrypt-output", RECIPIENT];
+    all.extend(args);
+    let output = kubemerge(&all);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let wrote = out.exists();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!output.status.success(), "{}", stderr);
+    assert!(stderr.contains("encrypt-output"), "{}", stderr);
+    assert!(!wrote, "plaintext was written");
+}
+
+#[test]
+fn encrypt_output_conflicts_with_split_output() {
+    assert_refused("encrypt-split-output", &["--split-output", "OUT"]);
+}
+
+#[test]
+fn encrypt_output_is_refused_with_the_split_subcommand() {
+    assert_refused("encrypt-split", &["split", "--dir", "OUT"]);
+}
@@ -0,0 +1,42 @@
+use kubemerge::decrypt::{Encryption, detect_encryption};
+use kubemerge::merge::{MergeOptions, parse_kubeconfig};
+use std::path::Path;
+
+const SOPS: &str = "
+apiVersion: ENC[AES256_GCM,data:Dmg=,iv:AAAA,tag:BBBB,type:str]
+kind: ENC[AES256_GCM,data:q1Nz8w==,iv:CCCC,tag:DDDD,type:str]
+sops:
+  mac: ENC[AES256_GCM,data:EEEE,iv:FFFF,tag:GGGG,type:str]
+  version: 3.9.0
+";
+
+const AGE_ARMORED: &str = "-----BEGIN AGE ENCRYPTED FILE-----
+YWdlLWVuY3J5cHRpb24ub3JnL3YxCg==
+-----END AGE ENCRYPTED FILE-----
+";
+
+#[test]
+fn encrypted_inputs_are_skipped_without_decrypt() {
+    let options = MergeOptions::default();
+    for (name, content) in [("sops.yaml", SOPS), ("age.yaml", AGE_ARMORED)] {
+        let parsed = parse_kubeconfig(Path::new(name), content.as_bytes().to_vec(), &options);
+        assert!(parsed.unwrap().is_none(), "{} was not skipped", name);
+    }
+}
+
+#[test]
+fn sops_is_detected_only_by_a_top_level_key() {
+    assert_eq!(detect_encryption(SOPS.as_bytes()), Some(Encryption::Sops));
+    let json = r#"{
+	"apiVersion": "ENC[AES256_GCM,data:Dmg=,type:str]",
+	"sops": {
+		"mac": "ENC[AES256_GCM,data:EEEE,type:str]"
+	}
+}"#;
+    assert_eq!(detect_encryption(json.as_bytes()), Some(Encryption::Sops));
+
+    let nested = "apiVersion: v1\nkind: Config\npreferences:\n  sops:\n    mac: x\n";
+    assert_eq!(detect_encryption(nested.as_bytes()), None);
+    let plain = "apiVersion: v1\nkind: Config\n";
+    assert_eq!(detect_encryption(plain.as_bytes()), None);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{assert_skipped, merge};
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::{Decision, SkipReason};
+
+const ENVIRONMENTS: &str = include_str!("fixtures/environments.yaml");
+
+fn added(decisions: &[Decision]) -> Vec<String> {
+    decisions
+        .iter()
+        .filter(|decision| matches!(decision, Decision::Added { .. }))
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// A single file with unique names takes the fast path; repeating one of
+/// its users sends the same content through the general path instead.
+#[test]
+fn single_file_fast_path_matches_general_path() {
+    let config: KubeConfig = serde_yml::from_str(ENVIRONMENTS).unwrap();
+    let mut repeated = config.clone();
+    let users = repeated.users.as_mut().unwrap();
+    users.push(users[0].clone());
+
+    let fast = merge(vec![config]);
+    let general = merge(vec![repeated]);
+
+    assert_eq!(
+        serde_yml::to_string(&fast.config).unwrap(),
+        serde_yml::to_string(&general.config).unwrap()
+    );
+    assert_eq!(fast.sources, general.sources);
+    assert_eq!(added(&fast.decisions), added(&general.decisions));
+    assert!(fast.skipped.is_empty());
+    assert_eq!(general.skipped.len(), 1);
+    assert_skipped(&general, "deployer", SkipReason::DuplicateIdentical);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::temp_dir;
+use kubemerge::utils::{ScanOptions, expand_input_pattern};
+use std::fs;
+
+#[test]
+fn patterns_are_filtered_like_a_directory_scan() {
+    let dir = temp_dir("input-pattern");
+    for team in ["a", "b"] {
+        fs::create_dir_all(dir.join(team)).unwrap();
+        for file in ["config.yaml", "notes.txt", "old.backup.yaml"] {
+            fs::write(dir.join(team).join(file), "apiVersion: v1\n").unwrap();
+        }
+    }
+    let options = ScanOptions {
+        exclude_patterns: vec!["backup".to_string()],
+        ..ScanOptions::default()
+    };
+
+    let pattern = format!("{}/{{a,b}}/*", dir.display());
+    let scanned = expand_input_pattern(&pattern, &options).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        scanned.files,
+        [dir.join("a/config.yaml"), dir.join("b/config.yaml")]
+    );
+    assert_eq!(
+        scanned.excluded,
+        [dir.join("a/old.backup.yaml"), dir.join("b/old.backup.yaml")]
+    );
+}
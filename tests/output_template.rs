@@ -0,0 +1,63 @@
+use kubemerge::config::KubeConfig;
+use kubemerge::split::{check_group_template, partition_by_group};
+use regex::Regex;
+use std::path::PathBuf;
+
+const ENVIRONMENTS: &str = include_str!("fixtures/environments.yaml");
+
+fn names<T>(entries: &Option<Vec<T>>, name: fn(&T) -> &String) -> Vec<&str> {
+    entries.iter().flatten().map(|e| name(e).as_str()).collect()
+}
+
+#[test]
+fn contexts_are_partitioned_with_their_dependencies() {
+    let config: KubeConfig = serde_yml::from_str(ENVIRONMENTS).unwrap();
+    let pattern = Regex::new("-(?P<env>prod|staging)-").unwrap();
+    check_group_template("out/{env}-config.yaml", &pattern).unwrap();
+
+    let (groups, unmatched) = partition_by_group(&config, &pattern, "out/{env}-config.yaml");
+    let paths: Vec<_> = groups.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(
+        paths,
+        [
+            PathBuf::from("out/prod-config.yaml"),
+            PathBuf::from("out/staging-config.yaml")
+        ]
+    );
+
+    let prod = &groups[0].1;
+    assert_eq!(
+        names(&prod.contexts, |c| &c.name),
+        ["app-prod-eu", "app-prod-us"]
+    );
+    assert_eq!(names(&prod.clusters, |c| &c.name), ["prod-eu", "prod-us"]);
+    assert_eq!(names(&prod.users, |u| &u.name), ["deployer"]);
+    assert_eq!(prod.current_context, "app-prod-eu");
+
+    let staging = &groups[1].1;
+    assert_eq!(names(&staging.contexts, |c| &c.name), ["app-staging-eu"]);
+    assert_eq!(staging.current_context, "app-staging-eu");
+
+    assert_eq!(names(&unmatched.contexts, |c| &c.name), ["kind-local"]);
+    assert_eq!(names(&unmatched.clusters, |c| &c.name), ["kind"]);
+    assert_eq!(names(&unmatched.users, |u| &u.name), ["kind-admin"]);
+}
+
+#[test]
+fn captures_are_made_safe_for_file_names() {
+    let mut config: KubeConfig = serde_yml::from_str(ENVIRONMENTS).unwrap();
+    config.contexts.as_mut().unwrap()[0].name = "arn:aws:eks:eu/prod".to_string();
+    let pattern = Regex::new("^arn:aws:eks:(.+)$").unwrap();
+
+    let (groups, _) = partition_by_group(&config, &pattern, "{1}.yaml");
+    assert_eq!(groups[0].0, PathBuf::from("eu_prod.yaml"));
+}
+
+#[test]
+fn templates_must_refer_to_captured_groups() {
+    let pattern = Regex::new("-(?P<env>prod)-").unwrap();
+    assert!(check_group_template("{1}.yaml", &pattern).is_ok());
+    assert!(check_group_template("config.yaml", &pattern).is_err());
+    assert!(check_group_template("{2}.yaml", &pattern).is_err());
+    assert!(check_group_template("{region}.yaml", &pattern).is_err());
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::merge;
+use kubemerge::config::KubeConfig;
+
+const TEAM: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: team
+  cluster: {server: "https://team:6443"}
+preferences:
+  colors: true
+  zebra: team
+  extensions:
+  - name: team
+    extension: {}
+"#;
+
+const PERSONAL: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: personal
+  cluster: {server: "https://personal:6443"}
+preferences:
+  zebra: personal
+  alpha: 1
+"#;
+
+fn merged_preferences() -> String {
+    let configs = [TEAM, PERSONAL]
+        .iter()
+        .map(|yaml| serde_yml::from_str::<KubeConfig>(yaml).unwrap())
+        .collect();
+    let config = merge(configs).config;
+    serde_yml::to_string(&config.preferences).unwrap()
+}
+
+#[test]
+fn overlapping_preferences_merge_deterministically() {
+    let first = merged_preferences();
+    assert_eq!(
+        first,
+        "alpha: 1\ncolors: true\nextensions:\n- name: team\n  extension: {}\nzebra: personal\n"
+    );
+    for _ in 0..10 {
+        assert_eq!(merged_preferences(), first);
+    }
+}
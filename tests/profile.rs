@@ -0,0 +1,79 @@
+mod common;
+
+use common::{kubemerge, temp_dir};
+use std::fs;
+
+#[test]
+fn missing_profile_is_reported_in_the_status_file() {
+    let dir = temp_dir("profile-missing");
+    let config = dir.join("config.yaml");
+    let status = dir.join("status.json");
+    fs::write(&config, "profiles:\n  work: {}\n").unwrap();
+
+    let output = kubemerge(&[
+        "--config",
+        config.to_str().unwrap(),
+        "--profile",
+        "home",
+        "--status-file",
+        status.to_str().unwrap(),
+    ]);
+    let record: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&status).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(record["result"], "failed");
+    assert!(
+        record.to_string().contains("Profile 'home' not found"),
+        "{}",
+        record
+    );
+}
+
+#[test]
+fn command_line_list_replaces_the_profile_list() {
+    let dir = temp_dir("profile-override");
+    let input = dir.join("in");
+    let config = dir.join("config.yaml");
+    let output = dir.join("merged.yaml");
+    fs::create_dir_all(&input).unwrap();
+    for (file, name) in [("a.json", "from-json"), ("b.yaml", "from-yaml")] {
+        let kubeconfig = format!(
+            "{{\"apiVersion\": \"v1\", \"kind\": \"Config\", \
+             \"clusters\": [{{\"name\": \"{0}\", \"cluster\": {{\"server\": \"https://{0}\"}}}}], \
+             \"contexts\": [{{\"name\": \"{0}\", \"context\": {{\"cluster\": \"{0}\", \"user\": \"\"}}}}]}}",
+            name
+        );
+        fs::write(input.join(file), kubeconfig).unwrap();
+    }
+    fs::write(
+        &config,
+        format!(
+            "profiles:\n  work:\n    input: {}\n    extensions: [json]\n",
+            input.display()
+        ),
+    )
+    .unwrap();
+
+    let result = kubemerge(&[
+        "--config",
+        config.to_str().unwrap(),
+        "--profile",
+        "work",
+        "--extensions",
+        "yaml",
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    let merged = fs::read_to_string(&output).unwrap_or_default();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert!(merged.contains("from-yaml"), "{}", merged);
+    assert!(!merged.contains("from-json"), "{}", merged);
+}
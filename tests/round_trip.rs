@@ -0,0 +1,47 @@
+mod common;
+
+use common::{assert_skipped, merge};
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::SkipReason;
+use serde_yml::Value;
+
+const ENTERPRISE_USER: &str = include_str!("fixtures/enterprise-user.yaml");
+
+#[test]
+fn enterprise_users_survive_a_round_trip() {
+    let original: KubeConfig = serde_yml::from_str(ENTERPRISE_USER).unwrap();
+
+    let first = serde_yml::to_string(&merge(vec![original.clone()]).config).unwrap();
+    let reparsed: KubeConfig = serde_yml::from_str(&first).unwrap();
+    assert_eq!(reparsed, original);
+
+    let second = serde_yml::to_string(&merge(vec![reparsed]).config).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn unmodeled_user_fields_count_as_content() {
+    let original: KubeConfig = serde_yml::from_str(ENTERPRISE_USER).unwrap();
+    let mut changed = original.clone();
+    let user = &mut changed.users.as_mut().unwrap()[0].user;
+    user.other.insert(
+        "client-pkcs12".to_string(),
+        Value::String("/etc/pki/other.p12".to_string()),
+    );
+
+    let result = merge(vec![original, changed]);
+    assert_skipped(&result, "corp-sso", SkipReason::DuplicateConflicting);
+}
+
+#[test]
+fn output_without_current_context_or_preferences_reads_back() {
+    let mut config: KubeConfig = serde_yml::from_str(ENTERPRISE_USER).unwrap();
+    config.current_context.clear();
+    config.preferences.clear();
+
+    let output = serde_yml::to_string(&config).unwrap();
+    assert!(!output.contains("current-context"));
+    assert!(!output.contains("preferences"));
+    let reparsed: KubeConfig = serde_yml::from_str(&output).unwrap();
+    assert_eq!(reparsed, config);
+}
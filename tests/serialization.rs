@@ -0,0 +1,59 @@
+mod common;
+
+use common::merge;
+use kubemerge::config::KubeConfig;
+
+const SCALARS: &str = r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: lab
+  cluster:
+    server: https://lab.example.com:6443
+    insecure-skip-tls-verify: true
+    x-retries: 3
+    x-ratio: 0.5
+    x-enabled: false
+    x-label: 'true'
+    x-unset: null
+    x-nested:
+      keep: 1
+      drop: null
+contexts:
+- name: lab
+  context:
+    cluster: lab
+    user: lab
+    x-unset: ~
+users:
+- name: lab
+  user:
+    token: lab-token
+current-context: lab
+"#;
+
+fn round_trip(yaml: &str) -> String {
+    let config: KubeConfig = serde_yml::from_str(yaml).unwrap();
+    serde_yml::to_string(&merge(vec![config]).config).unwrap()
+}
+
+#[test]
+fn scalars_keep_their_types_and_nulls_are_dropped() {
+    let output = round_trip(SCALARS);
+    assert!(output.contains("insecure-skip-tls-verify: true\n"));
+    assert!(output.contains("x-retries: 3\n"));
+    assert!(output.contains("x-ratio: 0.5\n"));
+    assert!(output.contains("x-enabled: false\n"));
+    assert!(output.contains("x-label: 'true'\n"));
+    assert!(output.contains("keep: 1\n"));
+    assert!(!output.contains("null"), "{}", output);
+    assert!(!output.contains("x-unset"), "{}", output);
+    assert!(!output.contains("drop"), "{}", output);
+}
+
+#[test]
+fn output_re_serializes_byte_identically() {
+    let first = round_trip(SCALARS);
+    let second = round_trip(&first);
+    assert_eq!(second, first);
+}
@@ -0,0 +1,62 @@
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::{MergeOptions, merge_configs};
+use std::path::PathBuf;
+
+const INPUTS: [&str; 3] = [
+    r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: zeta
+  cluster: {server: "https://zeta:6443"}
+contexts:
+- name: zeta
+  context: {cluster: zeta, user: zeta}
+users:
+- name: zeta
+  user: {token: z}
+current-context: zeta
+"#,
+    r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: alpha
+  cluster: {server: "https://alpha:6443"}
+- name: mid
+  cluster: {server: "https://mid:6443"}
+contexts:
+- name: alpha
+  context: {cluster: alpha, user: shared, namespace: apps}
+users:
+- name: shared
+  user: {username: admin, password: secret}
+preferences:
+  colors: true
+"#,
+    r#"
+apiVersion: v1
+kind: Config
+contexts:
+- name: mid
+  context: {cluster: mid, user: shared}
+"#,
+];
+
+fn merge_in_order(order: &[usize]) -> String {
+    let configs = order.iter().map(|&i| {
+        let config: KubeConfig = serde_yml::from_str(INPUTS[i]).unwrap();
+        (PathBuf::from(format!("{}.yaml", i)), config)
+    });
+    let mut result = merge_configs(configs, &MergeOptions::default()).unwrap();
+    result.config.sort_entries();
+    serde_yml::to_string(&result.config).unwrap()
+}
+
+#[test]
+fn stable_order_ignores_input_order() {
+    let expected = merge_in_order(&[0, 1, 2]);
+    for order in [[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+        assert_eq!(merge_in_order(&order), expected, "input order {:?}", order);
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{assert_skipped, merge};
+use kubemerge::config::KubeConfig;
+use kubemerge::merge::SkipReason;
+use kubemerge::rename::{coalesce_clusters, coalesce_renames};
+
+const SNI_CLUSTERS: &str = include_str!("fixtures/sni-clusters.yaml");
+
+#[test]
+fn clusters_differing_only_in_server_name_both_survive() {
+    let config: KubeConfig = serde_yml::from_str(SNI_CLUSTERS).unwrap();
+    let clusters = config.clusters.as_ref().unwrap();
+    assert_eq!(
+        clusters[0].cluster.tls_server_name.as_deref(),
+        Some("team-a.k8s.example.com")
+    );
+    assert!(clusters[0].cluster.other.is_empty());
+
+    let mut result = merge(vec![config]);
+    assert!(coalesce_renames(&result).is_empty());
+    coalesce_clusters(&mut result);
+    assert_eq!(result.config.clusters.as_ref().unwrap().len(), 2);
+
+    let output = serde_yml::to_string(&result.config).unwrap();
+    assert!(output.contains("tls-server-name: team-b.k8s.example.com"));
+}
+
+#[test]
+fn same_named_clusters_with_different_server_names_conflict() {
+    let original: KubeConfig = serde_yml::from_str(SNI_CLUSTERS).unwrap();
+    let mut changed = original.clone();
+    changed.clusters.as_mut().unwrap()[0]
+        .cluster
+        .tls_server_name = Some("other.k8s.example.com".to_string());
+
+    let result = merge(vec![original, changed]);
+    assert_skipped(&result, "team-a", SkipReason::DuplicateConflicting);
+}
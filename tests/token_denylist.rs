@@ -0,0 +1,96 @@
+mod common;
+
+use common::{assert_skipped, merge_with};
+use kubemerge::config::KubeConfig;
+use kubemerge::denylist::{TokenDenylist, scrub_denied_tokens, token_digest};
+use kubemerge::merge::{MergeOptions, SkipReason};
+use std::fs;
+use std::path::Path;
+
+fn config(token: &str) -> KubeConfig {
+    serde_yml::from_str(&format!(
+        r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: prod
+  cluster: {{server: "https://prod:6443"}}
+contexts:
+- name: prod
+  context: {{cluster: prod, user: deployer}}
+users:
+- name: deployer
+  user: {{token: {}}}
+"#,
+        token
+    ))
+    .unwrap()
+}
+
+fn load_denylist(name: &str, content: &str) -> Result<TokenDenylist, String> {
+    let path = std::env::temp_dir().join(format!(
+        "kubemerge-denylist-{}-{}.txt",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, content).unwrap();
+    let denylist = TokenDenylist::load(&path).map_err(|e| e.to_string());
+    fs::remove_file(&path).unwrap();
+    denylist
+}
+
+#[test]
+fn denylist_accepts_digests_and_plaintext() {
+    let digest = token_digest("revoked-by-digest");
+    let denylist = load_denylist(
+        "entries",
+        &format!(
+            "# revoked\nsha256:{}\n\nrevoked-in-plaintext\n",
+            digest.to_uppercase()
+        ),
+    )
+    .unwrap();
+    assert!(denylist.contains("revoked-by-digest"));
+    assert!(denylist.contains(" revoked-in-plaintext\n"));
+    assert!(!denylist.contains("still-valid"));
+
+    let error = load_denylist("invalid", "sha256:abc\n").unwrap_err();
+    assert!(
+        error.contains(":1: expected a SHA-256 hex digest"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn clean_duplicate_replaces_denied_user() {
+    let options = MergeOptions {
+        token_denylist: Some(load_denylist("merge", "revoked\n").unwrap()),
+        ..MergeOptions::default()
+    };
+    let result = merge_with(vec![config("revoked"), config("valid")], &options);
+    assert_skipped(&result, "deployer", SkipReason::DeniedToken);
+
+    let users = result.config.users.unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].user.token.as_deref(), Some("valid"));
+}
+
+#[test]
+fn strict_denylist_fails_instead_of_dropping() {
+    let denylist = load_denylist("strict", "revoked\n").unwrap();
+    let source = Path::new("team.yaml");
+
+    let mut lenient = config("revoked");
+    let skipped = scrub_denied_tokens(&mut lenient, source, &denylist, false).unwrap();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].source_file, source);
+    assert!(lenient.users.unwrap().is_empty());
+
+    let mut strict = config("revoked");
+    let error = scrub_denied_tokens(&mut strict, source, &denylist, true).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "User 'deployer' from team.yaml uses a denylisted token"
+    );
+}